@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::probe::{ProbeResult, Stream};
+use crate::Input;
+
+/// Compares file names so that `clip2` comes before `clip10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(c) = a.next_if(|c| c.is_ascii_digit()) {
+                    x_digits.push(c);
+                }
+                let mut y_digits = String::new();
+                while let Some(c) = b.next_if(|c| c.is_ascii_digit()) {
+                    y_digits.push(c);
+                }
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Files directly under `dir` that `input` accepts, in natural order. Hidden files are skipped.
+pub fn list_directory(dir: &Path, input: &Input) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && input.accepts(path))
+        .filter(|path| {
+            !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'))
+        })
+        .collect();
+    files.sort_by(|a, b| {
+        natural_cmp(
+            a.file_name().unwrap().to_string_lossy().as_ref(),
+            b.file_name().unwrap().to_string_lossy().as_ref(),
+        )
+    });
+    Ok(files)
+}
+
+/// Writes an ffconcat list of `paths` to `list_path`. Paths are made absolute, so the list
+/// has to be read with `-safe 0`.
+pub fn write_list(paths: &[PathBuf], list_path: &Path) -> io::Result<()> {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for path in paths {
+        let path = std::path::absolute(path)?;
+        // Inside single quotes, a quote is written by closing the quote, escaping it and reopening.
+        list.push_str(
+            format!(
+                "file '{}'\n",
+                path.display().to_string().replace('\'', r"'\''")
+            )
+            .as_str(),
+        );
+    }
+    fs::write(list_path, list)
+}
+
+fn describe_video(stream: &Stream) -> String {
+    format!(
+        "{} {}x{} {} {} fps",
+        stream.codec_name.as_deref().unwrap_or("?"),
+        stream.width.unwrap_or(0),
+        stream.height.unwrap_or(0),
+        stream.pix_fmt.as_deref().unwrap_or("?"),
+        stream.r_frame_rate.as_deref().unwrap_or("?"),
+    )
+}
+
+fn describe_audio(stream: &Stream) -> String {
+    format!(
+        "{} {}Hz {}ch",
+        stream.codec_name.as_deref().unwrap_or("?"),
+        stream.sample_rate.as_deref().unwrap_or("?"),
+        stream.channels.unwrap_or(0),
+    )
+}
+
+fn describe(probe_result: &ProbeResult) -> Vec<String> {
+    probe_result
+        .streams_of_type("video")
+        .map(|stream| format!("video: {}", describe_video(stream)))
+        .chain(
+            probe_result
+                .streams_of_type("audio")
+                .map(|stream| format!("audio: {}", describe_audio(stream))),
+        )
+        .collect()
+}
+
+/// Describes every file whose streams differ from the first file's, which the concat demuxer
+/// cannot join without re-encoding.
+pub fn find_mismatches(probes: &[(PathBuf, ProbeResult)]) -> Vec<String> {
+    let Some((first_path, first)) = probes.first() else {
+        return Vec::new();
+    };
+    let expected = describe(first);
+    probes[1..]
+        .iter()
+        .filter(|(_, probe_result)| describe(probe_result) != expected)
+        .map(|(path, probe_result)| {
+            format!(
+                "{} [{}] differs from {} [{}]",
+                path.display(),
+                describe(probe_result).join(", "),
+                first_path.display(),
+                expected.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Arguments that join `inputs` with the concat filter, converting every input to the
+/// resolution, frame rate and audio format of the first one. Audio is dropped unless every
+/// input has it.
+pub fn reencode_args(probes: &[(PathBuf, ProbeResult)]) -> Vec<String> {
+    let first = &probes[0].1;
+    let video = first.streams_of_type("video").next();
+    let audio = first.streams_of_type("audio").next();
+    let has_video = video.is_some()
+        && probes
+            .iter()
+            .all(|(_, probe_result)| probe_result.streams_of_type("video").next().is_some());
+    let has_audio = audio.is_some()
+        && probes
+            .iter()
+            .all(|(_, probe_result)| probe_result.streams_of_type("audio").next().is_some());
+
+    let mut args = Vec::new();
+    for (path, _) in probes {
+        args.push("-i".to_string());
+        args.push(path.display().to_string());
+    }
+
+    let mut filters = Vec::new();
+    let mut concat_inputs = String::new();
+    for idx in 0..probes.len() {
+        if let (true, Some(video)) = (has_video, video) {
+            let (width, height) = (video.width.unwrap_or(1920), video.height.unwrap_or(1080));
+            filters.push(format!(
+                "[{idx}:v:0]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={},format={}[v{idx}]",
+                video.r_frame_rate.as_deref().unwrap_or("30"),
+                video.pix_fmt.as_deref().unwrap_or("yuv420p"),
+            ));
+            concat_inputs.push_str(format!("[v{}]", idx).as_str());
+        }
+        if let (true, Some(audio)) = (has_audio, audio) {
+            filters.push(format!(
+                "[{idx}:a:0]aresample={},aformat=channel_layouts={}[a{idx}]",
+                audio.sample_rate.as_deref().unwrap_or("48000"),
+                if audio.channels == Some(1) {
+                    "mono"
+                } else {
+                    "stereo"
+                },
+            ));
+            concat_inputs.push_str(format!("[a{}]", idx).as_str());
+        }
+    }
+    let mut outputs = String::new();
+    if has_video {
+        outputs.push_str("[v]");
+    }
+    if has_audio {
+        outputs.push_str("[a]");
+    }
+    filters.push(format!(
+        "{}concat=n={}:v={}:a={}{}",
+        concat_inputs,
+        probes.len(),
+        has_video as u8,
+        has_audio as u8,
+        outputs
+    ));
+
+    args.push("-filter_complex".to_string());
+    args.push(filters.join(";"));
+    if has_video {
+        args.extend(["-map".to_string(), "[v]".to_string()]);
+    }
+    if has_audio {
+        args.extend(["-map".to_string(), "[a]".to_string()]);
+    }
+    args
+}
@@ -0,0 +1,418 @@
+//! Conditions written in `when:`, such as `input.height > 720 && var.audio != 'copy'`.
+//!
+//! A condition compares variables and literals with `==`, `!=`, `<`, `<=`, `>` and `>=`, and
+//! combines them with `&&`, `||`, `!` and parentheses. Variables are `input.<field>`, read from
+//! ffprobe, and `var.<name>`, given by the user. A variable that has no value is `null`, which
+//! equals only `null` and is neither less nor greater than anything.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::probe::ProbeResult;
+
+/// Fields of `input.<field>`.
+pub const INPUT_FIELDS: [&str; 13] = [
+    "width",
+    "height",
+    "fps",
+    "video_codec",
+    "pix_fmt",
+    "audio_codec",
+    "audio_bitrate",
+    "audio_channels",
+    "sample_rate",
+    "duration",
+    "has_video",
+    "has_audio",
+    "has_subtitle",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    /// The value as a number. Text that looks like a number counts, so that `var.<name>` given
+    /// on the command line can be compared with numbers.
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(number) => *number != 0.0,
+            Value::Text(text) => !text.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Values of the variables that conditions refer to.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables::default()
+    }
+
+    /// Sets `input.<field>` from the first video and audio streams of `probe_result`.
+    pub fn with_input(mut self, probe_result: &ProbeResult) -> Variables {
+        let video = probe_result.streams_of_type("video").next();
+        let audio = probe_result.streams_of_type("audio").next();
+        let text = |value: Option<&String>| value.map_or(Value::Null, |v| Value::Text(v.clone()));
+        let number = |value: Option<f64>| value.map_or(Value::Null, Value::Number);
+        let fields = [
+            (
+                "width",
+                number(video.and_then(|s| s.width).map(|w| w as f64)),
+            ),
+            (
+                "height",
+                number(video.and_then(|s| s.height).map(|h| h as f64)),
+            ),
+            (
+                "fps",
+                number(video.and_then(|s| parse_rate(s.r_frame_rate.as_ref()?))),
+            ),
+            (
+                "video_codec",
+                text(video.and_then(|s| s.codec_name.as_ref())),
+            ),
+            ("pix_fmt", text(video.and_then(|s| s.pix_fmt.as_ref()))),
+            (
+                "audio_codec",
+                text(audio.and_then(|s| s.codec_name.as_ref())),
+            ),
+            (
+                "audio_bitrate",
+                number(probe_result.audio_bitrate().map(|b| b as f64)),
+            ),
+            (
+                "audio_channels",
+                number(audio.and_then(|s| s.channels).map(|c| c as f64)),
+            ),
+            (
+                "sample_rate",
+                number(audio.and_then(|s| s.sample_rate.as_ref()?.parse().ok())),
+            ),
+            ("duration", number(probe_result.duration())),
+            ("has_video", Value::Bool(video.is_some())),
+            ("has_audio", Value::Bool(audio.is_some())),
+            (
+                "has_subtitle",
+                Value::Bool(probe_result.streams_of_type("subtitle").next().is_some()),
+            ),
+        ];
+        for (field, value) in fields {
+            self.values.insert(format!("input.{}", field), value);
+        }
+        self
+    }
+
+    /// Sets `var.<name>` for each of `vars`.
+    pub fn with_vars<'a>(
+        mut self,
+        vars: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Variables {
+        for (name, value) in vars {
+            self.values
+                .insert(format!("var.{}", name), Value::Text(value.clone()));
+        }
+        self
+    }
+
+    fn get(&self, name: &str) -> Value {
+        self.values.get(name).cloned().unwrap_or(Value::Null)
+    }
+}
+
+/// Parses `r_frame_rate` such as `30000/1001`.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (numerator, denominator) = rate.split_once('/')?;
+    let denominator: f64 = denominator.parse().ok()?;
+    (denominator != 0.0).then_some(numerator.parse::<f64>().ok()? / denominator)
+}
+
+/// Checks that `condition` is well-formed and refers only to known variables.
+pub fn check(condition: &str) -> Result<(), String> {
+    let expr = parse(condition)?;
+    let mut names = Vec::new();
+    expr.variables(&mut names);
+    for name in names {
+        let known = match name.split_once('.') {
+            Some(("input", field)) => INPUT_FIELDS.contains(&field),
+            Some(("var", var)) => !var.is_empty(),
+            _ => false,
+        };
+        if !known {
+            return Err(format!(
+                "{} in \"{}\" is not a variable. Use input.<field> or var.<name>.",
+                name, condition
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates `condition` with `variables`.
+pub fn evaluate(condition: &str, variables: &Variables) -> Result<bool, String> {
+    parse(condition)?
+        .evaluate(variables)
+        .map(|value| value.is_truthy())
+        .map_err(|error| format!("{} in \"{}\"", error, condition))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(Value),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 13] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "=", "&",
+];
+
+fn tokenize(condition: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = condition.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        if c == '\'' || c == '"' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or(format!("A string is not closed in \"{}\".", condition))?;
+            tokens.push(Token::Literal(Value::Text(rest[1..=end].to_string())));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit() || c == '-' {
+            let end = rest[1..]
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .map_or(rest.len(), |end| end + 1);
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("{} in \"{}\" is not a number.", &rest[..end], condition))?;
+            tokens.push(Token::Literal(Value::Number(number)));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+                .unwrap_or(rest.len());
+            tokens.push(match &rest[..end] {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "null" => Token::Literal(Value::Null),
+                name => Token::Name(name.to_string()),
+            });
+            rest = &rest[end..];
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+                .ok_or(format!("{} in \"{}\" is not understood.", c, condition))?;
+            if *operator == "=" || *operator == "&" {
+                return Err(format!(
+                    "Use {} instead of {} in \"{}\".",
+                    operator.repeat(2),
+                    operator,
+                    condition
+                ));
+            }
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn variables(&self, names: &mut Vec<String>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Variable(name) => names.push(name.clone()),
+            Expr::Not(expr) => expr.variables(names),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::Compare(_, left, right) => {
+                left.variables(names);
+                right.variables(names);
+            }
+        }
+    }
+
+    fn evaluate(&self, variables: &Variables) -> Result<Value, String> {
+        Ok(match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Variable(name) => variables.get(name),
+            Expr::Not(expr) => Value::Bool(!expr.evaluate(variables)?.is_truthy()),
+            Expr::And(left, right) => Value::Bool(
+                left.evaluate(variables)?.is_truthy() && right.evaluate(variables)?.is_truthy(),
+            ),
+            Expr::Or(left, right) => Value::Bool(
+                left.evaluate(variables)?.is_truthy() || right.evaluate(variables)?.is_truthy(),
+            ),
+            Expr::Compare(operator, left, right) => {
+                let left = left.evaluate(variables)?;
+                let right = right.evaluate(variables)?;
+                Value::Bool(compare(operator, &left, &right)?)
+            }
+        })
+    }
+}
+
+fn compare(operator: &str, left: &Value, right: &Value) -> Result<bool, String> {
+    let equal = match (left, right) {
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        _ => match (left.as_number(), right.as_number()) {
+            (Some(left), Some(right)) => left == right,
+            _ => left.to_string() == right.to_string(),
+        },
+    };
+    match operator {
+        "==" => return Ok(equal),
+        "!=" => return Ok(!equal),
+        _ => {}
+    }
+    if *left == Value::Null || *right == Value::Null {
+        return Ok(false);
+    }
+    let (Some(left_number), Some(right_number)) = (left.as_number(), right.as_number()) else {
+        return Err(format!(
+            "{} {} {} compares something other than numbers",
+            left, operator, right
+        ));
+    };
+    Ok(match operator {
+        "<" => left_number < right_number,
+        "<=" => left_number <= right_number,
+        ">" => left_number > right_number,
+        _ => left_number >= right_number,
+    })
+}
+
+fn parse(condition: &str) -> Result<Expr, String> {
+    let tokens = tokenize(condition)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let expr = parser
+        .or()
+        .map_err(|error| format!("{} in \"{}\".", error, condition))?;
+    if parser.position < tokens.len() {
+        return Err(format!(
+            "Unexpected {} in \"{}\".",
+            describe(&tokens[parser.position]),
+            condition
+        ));
+    }
+    Ok(expr)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Name(name) => name.clone(),
+        Token::Literal(value) => value.to_string(),
+        Token::Operator(operator) => operator.to_string(),
+    }
+}
+
+/// Recursive descent parser where `||` binds looser than `&&`, which binds looser than `!` and
+/// the comparisons.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, operator: &'static str) -> bool {
+        if self.tokens.get(self.position) == Some(&Token::Operator(operator)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let left = self.primary()?;
+        for operator in ["==", "!=", "<=", ">=", "<", ">"] {
+            if self.eat(operator) {
+                let right = self.primary()?;
+                return Ok(Expr::Compare(operator, Box::new(left), Box::new(right)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or("Unexpected end".to_string())?;
+        self.position += 1;
+        match token {
+            Token::Name(name) => Ok(Expr::Variable(name.clone())),
+            Token::Literal(value) => Ok(Expr::Literal(value.clone())),
+            Token::Operator("(") => {
+                let expr = self.or()?;
+                if !self.eat(")") {
+                    return Err("A parenthesis is not closed".to_string());
+                }
+                Ok(expr)
+            }
+            Token::Operator(operator) => Err(format!("Unexpected {}", operator)),
+        }
+    }
+}
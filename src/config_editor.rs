@@ -0,0 +1,226 @@
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use super::{Command, CommandOption, Encoder};
+
+// The config file is edited as text rather than re-serialized as a whole so that
+// comments, blank lines and the order of commands written by the user survive.
+
+#[derive(Serialize)]
+struct OptionsField<'a> {
+    options: &'a [CommandOption],
+}
+
+#[derive(Serialize)]
+struct EncodersField<'a> {
+    encoders: &'a [Encoder],
+}
+
+struct CommandBlock {
+    lines: Range<usize>,
+    item_indent: usize,
+    title: String,
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+fn indent_lines(text: &str, indent: usize) -> Vec<String> {
+    text.lines()
+        .map(|line| format!("{}{}", " ".repeat(indent), line))
+        .collect()
+}
+
+fn find_command_blocks(lines: &[&str]) -> Result<Vec<CommandBlock>, String> {
+    let re = Regex::new(r"^commands:\s*(#.*)?$").unwrap();
+    let start = match lines.iter().position(|line| re.is_match(line)) {
+        Some(idx) => idx + 1,
+        None => return Err("commands field was not found in config file.".to_string()),
+    };
+
+    let mut blocks = Vec::new();
+    let mut item_indent = None;
+    let mut current: Option<usize> = None;
+    let mut last_content = start;
+    for (idx, line) in lines.iter().enumerate().skip(start) {
+        if is_blank_or_comment(line) {
+            continue;
+        }
+        let indent = indent_of(line);
+        let is_item = line.trim_start().starts_with('-');
+        let item_indent = *item_indent.get_or_insert(indent);
+        if indent < item_indent || (indent == item_indent && !is_item) {
+            break;
+        }
+        if indent == item_indent && is_item {
+            if let Some(block_start) = current {
+                blocks.push(block_start..last_content + 1);
+            }
+            current = Some(idx);
+        }
+        last_content = idx;
+    }
+    if let Some(block_start) = current {
+        blocks.push(block_start..last_content + 1);
+    }
+
+    blocks
+        .into_iter()
+        .map(|range| {
+            let item_indent = indent_of(lines[range.start]);
+            let text: Vec<&str> = lines[range.clone()]
+                .iter()
+                .map(|line| line.get(item_indent..).unwrap_or(line.trim_start()))
+                .collect();
+            let parsed: Vec<Command> = serde_yaml::from_str(text.join("\n").as_str())
+                .map_err(|error| format!("Unable to parse a command in config file: {}", error))?;
+            Ok(CommandBlock {
+                lines: range,
+                item_indent,
+                title: parsed[0].title.clone(),
+            })
+        })
+        .collect()
+}
+
+fn write_lines(path: &Path, lines: Vec<String>, had_trailing_newline: bool) -> Result<(), String> {
+    let mut text = lines.join("\n");
+    if had_trailing_newline {
+        text.push('\n');
+    }
+    fs::write(path, text).map_err(|error| error.to_string())
+}
+
+/// Replaces the `options` of the command titled `title` in the config file at `path`.
+/// Only the `options` field is rewritten, so comments on other fields are kept.
+pub fn update_command_options(
+    path: &Path,
+    title: &str,
+    options: &[CommandOption],
+) -> Result<(), String> {
+    let field_yaml =
+        serde_yaml::to_string(&OptionsField { options }).map_err(|error| error.to_string())?;
+    update_command_field(path, title, "options", field_yaml, |command| {
+        command.options = options.to_vec()
+    })
+}
+
+/// Replaces the `encoders` of the command titled `title` in the config file at `path`, in the
+/// same way as [`update_command_options`].
+pub fn update_command_encoders(
+    path: &Path,
+    title: &str,
+    encoders: &[Encoder],
+) -> Result<(), String> {
+    let field_yaml =
+        serde_yaml::to_string(&EncodersField { encoders }).map_err(|error| error.to_string())?;
+    update_command_field(path, title, "encoders", field_yaml, |command| {
+        command.encoders = encoders.to_vec()
+    })
+}
+
+/// Replaces the field `key` of the command titled `title` with `field_yaml`, or rewrites the
+/// whole command changed by `update` when the field is not a plain block under the command.
+fn update_command_field(
+    path: &Path,
+    title: &str,
+    key: &str,
+    field_yaml: String,
+    update: impl FnOnce(&mut Command),
+) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let lines: Vec<&str> = text.lines().collect();
+    let block = find_command_blocks(&lines)?
+        .into_iter()
+        .find(|block| block.title == title)
+        .ok_or(format!("{} was not found in config file.", title))?;
+
+    let after_dash = &lines[block.lines.start][block.item_indent + 1..];
+    let key_indent = block.item_indent + 1 + indent_of(after_dash);
+
+    let mut new_lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let key_re = Regex::new(format!(r"^{}:\s*(#.*)?$", key).as_str()).unwrap();
+    let field_start = (block.lines.start + 1..block.lines.end).find(|idx| {
+        indent_of(lines[*idx]) == key_indent && key_re.is_match(lines[*idx].trim_start())
+    });
+
+    match field_start {
+        Some(field_start) => {
+            let mut field_end = field_start + 1;
+            while field_end < block.lines.end {
+                let line = lines[field_end];
+                let indent = indent_of(line);
+                if !is_blank_or_comment(line)
+                    && (indent < key_indent
+                        || (indent == key_indent && !line.trim_start().starts_with('-')))
+                {
+                    break;
+                }
+                field_end += 1;
+            }
+            while field_end > field_start + 1 && is_blank_or_comment(lines[field_end - 1]) {
+                field_end -= 1;
+            }
+            new_lines.splice(
+                field_start..field_end,
+                indent_lines(field_yaml.as_str(), key_indent),
+            );
+        }
+        None => {
+            // Fall back to rewriting the whole command when the field is not laid out
+            // as a plain block under the command.
+            let mut command: Vec<Command> = serde_yaml::from_str(
+                lines[block.lines.clone()]
+                    .iter()
+                    .map(|line| line.get(block.item_indent..).unwrap_or(line.trim_start()))
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+                    .as_str(),
+            )
+            .map_err(|error| error.to_string())?;
+            update(&mut command[0]);
+            let command_yaml =
+                serde_yaml::to_string(&command).map_err(|error| error.to_string())?;
+            new_lines.splice(
+                block.lines.clone(),
+                indent_lines(command_yaml.as_str(), block.item_indent),
+            );
+        }
+    }
+
+    write_lines(path, new_lines, text.ends_with('\n'))
+}
+
+/// Appends `command` after the last command in the config file at `path`.
+pub fn append_command(path: &Path, command: &Command) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let lines: Vec<&str> = text.lines().collect();
+    let blocks = find_command_blocks(&lines)?;
+    let (insert_at, item_indent) = match blocks.last() {
+        Some(block) => (block.lines.end, block.item_indent),
+        None => {
+            let re = Regex::new(r"^commands:\s*(#.*)?$").unwrap();
+            (
+                lines.iter().position(|line| re.is_match(line)).unwrap() + 1,
+                2,
+            )
+        }
+    };
+    let command_yaml = serde_yaml::to_string(&vec![command]).map_err(|error| error.to_string())?;
+
+    let mut new_lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    new_lines.splice(
+        insert_at..insert_at,
+        indent_lines(command_yaml.as_str(), item_indent),
+    );
+    write_lines(path, new_lines, true)
+}
@@ -0,0 +1,70 @@
+//! Choosing the video encoder of a command from the ones that the local ffmpeg has.
+
+use std::collections::HashSet;
+use std::process::Command as ProcessCommand;
+
+use crate::{CommandOption, Encoder};
+
+/// Names of the encoders in the output of `ffmpeg -encoders`, which lists them after a line of
+/// dashes as `<flags> <name> <description>`, e.g. ` V....D libx264  libx264 H.264 ...`.
+pub fn parse_encoders(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Encoders that `ffmpeg_path` was built with.
+pub fn list_encoders(ffmpeg_path: &str) -> Result<HashSet<String>, String> {
+    let output = ProcessCommand::new(ffmpeg_path)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map_err(|error| format!("Unable to run {}: {}", ffmpeg_path, error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} could not list the encoders: {}",
+            ffmpeg_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_encoders(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The first of `candidates` that is in `available`.
+pub fn choose<'a>(candidates: &'a [Encoder], available: &HashSet<String>) -> Option<&'a Encoder> {
+    candidates
+        .iter()
+        .find(|encoder| available.contains(&encoder.name))
+}
+
+/// `-c:v <name>` followed by the options of `encoder`, to be put before the options of the
+/// command.
+pub fn encoder_options(encoder: &Encoder) -> Vec<CommandOption> {
+    let mut options = vec![CommandOption {
+        flag: "-c:v".to_string(),
+        value: encoder.name.clone(),
+        when: None,
+    }];
+    options.extend(encoder.options.iter().cloned());
+    options
+}
+
+/// Whether `flag` sets the codec of `stream`, `'v'` or `'a'`, or of any stream when `stream` is
+/// `None`. `-c` and `-codec` without a stream specifier set the codec of every stream.
+pub fn is_codec_flag(flag: &str, stream: Option<char>) -> bool {
+    match flag {
+        "-c" | "-codec" => true,
+        "-vcodec" => stream != Some('a'),
+        "-acodec" => stream != Some('v'),
+        _ => match flag
+            .strip_prefix("-c:")
+            .or_else(|| flag.strip_prefix("-codec:"))
+        {
+            Some(specifier) => stream.is_none_or(|stream| specifier.starts_with(stream)),
+            None => false,
+        },
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::io;
+
+/// Exit code for Ctrl+C, following the shell convention of 128 + SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Why kffmpeg stopped without finishing its work. Each kind has its own process exit code.
+///
+/// The messages are meant to be shown to the user as they are.
+#[derive(Debug)]
+pub enum Error {
+    /// The config file is missing, unreadable or has invalid commands.
+    Config(String),
+    /// Arguments or input files are invalid.
+    Validation(String),
+    /// ffmpeg was found neither on PATH nor at `ffmpeg_path`.
+    FfmpegNotFound(String),
+    /// A step exited with a non-zero code, or with `None` when it could not be started or was killed.
+    FfmpegFailed(Option<i32>),
+    /// ffmpeg succeeded, but the output failed the checks of `verify`.
+    VerificationFailed(String),
+    /// Some jobs of a batch failed.
+    JobsFailed { failed: usize, total: usize },
+    /// The user quit at a prompt or chose not to run the command.
+    Aborted,
+    /// Ctrl+C was pressed.
+    Interrupted,
+    /// Any other failure, such as an I/O error or stdin being closed before an answer was given.
+    Other(String),
+}
+
+impl Error {
+    /// Process exit code for the error. The codes are listed in the README, so they must not change.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) => 1,
+            Error::Config(_) => 3,
+            Error::Validation(_) => 4,
+            Error::FfmpegNotFound(_) => 5,
+            Error::FfmpegFailed(_) | Error::VerificationFailed(_) | Error::JobsFailed { .. } => 6,
+            Error::Aborted => 7,
+            Error::Interrupted => INTERRUPTED_EXIT_CODE,
+        }
+    }
+
+    /// Name of the kind of the error, such as `ffmpeg_failed`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config",
+            Error::Validation(_) => "validation",
+            Error::FfmpegNotFound(_) => "ffmpeg_not_found",
+            Error::FfmpegFailed(_) => "ffmpeg_failed",
+            Error::VerificationFailed(_) => "verification_failed",
+            Error::JobsFailed { .. } => "jobs_failed",
+            Error::Aborted => "aborted",
+            Error::Interrupted => "interrupted",
+            Error::Other(_) => "other",
+        }
+    }
+
+    /// Exit code of ffmpeg to record in the history.
+    pub fn ffmpeg_exit_code(&self) -> Option<i32> {
+        match self {
+            Error::FfmpegFailed(code) => *code,
+            Error::VerificationFailed(_) => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "Config error: {}", message),
+            Error::Validation(message) => write!(f, "Invalid input: {}", message),
+            Error::FfmpegNotFound(path) => {
+                write!(f, "ffmpeg is not found on PATH or at {}.", path)
+            }
+            Error::FfmpegFailed(Some(code)) => write!(f, "ffmpeg failed with exit code {}.", code),
+            Error::FfmpegFailed(None) => write!(f, "ffmpeg failed."),
+            Error::VerificationFailed(message) => {
+                write!(f, "Output verification failed: {}", message)
+            }
+            Error::JobsFailed { failed, total } => {
+                write!(f, "{} of {} jobs failed.", failed, total)
+            }
+            Error::Aborted => write!(f, "Aborted by the user."),
+            Error::Interrupted => write!(f, "Interrupted."),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Other(error.to_string())
+    }
+}
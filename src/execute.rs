@@ -0,0 +1,220 @@
+//! Running expanded steps with ffmpeg.
+
+use regex::Regex;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::process::{Command as ProcessCommand, Stdio};
+use std::sync::OnceLock;
+
+use crate::error::Error;
+use crate::job_log::JobLog;
+use crate::template::ExpandedStep;
+
+/// A progress line of ffmpeg, such as
+/// `frame=  240 fps= 60 size=    1024kB time=00:00:08.00 bitrate=1048.6kbits/s speed=2.0x`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub frame: Option<u64>,
+    /// Position in the output in seconds.
+    pub time: Option<f64>,
+    pub size: Option<String>,
+    pub bitrate: Option<String>,
+    /// Multiple of the real time, e.g. `2.0` for `speed=2.0x`.
+    pub speed: Option<f64>,
+}
+
+impl Progress {
+    /// Parses a line of stderr. Lines with neither `frame=` nor `time=` are not progress.
+    pub fn parse(line: &str) -> Option<Progress> {
+        static FIELD: OnceLock<Regex> = OnceLock::new();
+        let re = FIELD.get_or_init(|| Regex::new(r"(\w+)=\s*(\S+)").unwrap());
+        let fields: Vec<(&str, &str)> = re
+            .captures_iter(line)
+            .map(|captures| {
+                (
+                    captures.get(1).unwrap().as_str(),
+                    captures.get(2).unwrap().as_str(),
+                )
+            })
+            .collect();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+        if field("frame").is_none() && field("time").is_none() {
+            return None;
+        }
+        Some(Progress {
+            frame: field("frame").and_then(|frame| frame.parse().ok()),
+            time: field("time").and_then(parse_time),
+            size: field("size").map(str::to_string),
+            bitrate: field("bitrate").map(str::to_string),
+            speed: field("speed").and_then(|speed| speed.trim_end_matches('x').parse().ok()),
+        })
+    }
+}
+
+/// Parses `HH:MM:SS.ss` into seconds.
+fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// Receives what happens while steps run. Every method does nothing by default, and `()`
+/// ignores everything.
+pub trait Observer {
+    /// Where the stdout of ffmpeg goes. It is inherited by default.
+    fn stdout(&mut self) -> Stdio {
+        Stdio::inherit()
+    }
+
+    /// Called before step `index` of the steps starts.
+    fn step_started(&mut self, _index: usize, _step: &ExpandedStep) {}
+
+    /// Called with each line of stderr, where the progress updates that ffmpeg ends with `\r`
+    /// also count as lines. Only called when the steps run with a log.
+    fn stderr_line(&mut self, _step: &ExpandedStep, _line: &str) {}
+
+    /// Called for each line of stderr that is a progress line.
+    fn progress(&mut self, _step: &ExpandedStep, _progress: &Progress) {}
+
+    /// Called after step `index` exits. `exit_code` is `None` when it could not be started or
+    /// was killed by a signal.
+    fn step_finished(&mut self, _index: usize, _step: &ExpandedStep, _exit_code: Option<i32>) {}
+}
+
+impl Observer for () {}
+
+/// Runs the process of a step. [`run_steps`] goes through it for every step, so that steps can
+/// be recorded in tests or shown without running them.
+pub trait Executor {
+    /// Runs `step` with its stdout sent to `stdout`, and returns its exit code, which is `None`
+    /// when it was killed by a signal. The stderr is copied to `log` with `on_line` called for
+    /// each line when `log` is given, and goes straight to the terminal otherwise.
+    fn execute(
+        &self,
+        step: &ExpandedStep,
+        log: Option<&mut JobLog>,
+        stdout: Stdio,
+        on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>>;
+}
+
+/// Spawns each step as a child process. This is what the CLI uses.
+pub struct ProcessExecutor;
+
+impl Executor for ProcessExecutor {
+    fn execute(
+        &self,
+        step: &ExpandedStep,
+        log: Option<&mut JobLog>,
+        stdout: Stdio,
+        on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>> {
+        let mut process = ProcessCommand::new(step.argv[0].clone());
+        process.args(&step.argv[1..]).stdout(stdout);
+        let status = match log {
+            Some(log) => log.run(step.name.as_str(), &step.argv, &mut process, on_line)?,
+            None => process.stderr(Stdio::inherit()).status()?,
+        };
+        Ok(status.code())
+    }
+}
+
+/// Runs nothing and reports every step as succeeded, to show what would run.
+pub struct DryRunExecutor;
+
+impl Executor for DryRunExecutor {
+    fn execute(
+        &self,
+        _step: &ExpandedStep,
+        _log: Option<&mut JobLog>,
+        _stdout: Stdio,
+        _on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>> {
+        Ok(Some(0))
+    }
+}
+
+/// Records the steps instead of running them, for tests. Each step exits with the next of the
+/// given exit codes, or with 0 when they run out.
+#[derive(Default)]
+pub struct RecordingExecutor {
+    steps: RefCell<Vec<ExpandedStep>>,
+    exit_codes: RefCell<VecDeque<i32>>,
+}
+
+impl RecordingExecutor {
+    pub fn new() -> RecordingExecutor {
+        RecordingExecutor::default()
+    }
+
+    /// A recorder whose steps exit with `exit_codes` in order.
+    pub fn with_exit_codes(exit_codes: impl IntoIterator<Item = i32>) -> RecordingExecutor {
+        RecordingExecutor {
+            steps: RefCell::new(Vec::new()),
+            exit_codes: RefCell::new(exit_codes.into_iter().collect()),
+        }
+    }
+
+    /// The steps executed so far.
+    pub fn steps(&self) -> Vec<ExpandedStep> {
+        self.steps.borrow().clone()
+    }
+}
+
+impl Executor for RecordingExecutor {
+    fn execute(
+        &self,
+        step: &ExpandedStep,
+        _log: Option<&mut JobLog>,
+        _stdout: Stdio,
+        _on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>> {
+        self.steps.borrow_mut().push(step.clone());
+        Ok(Some(self.exit_codes.borrow_mut().pop_front().unwrap_or(0)))
+    }
+}
+
+/// Runs the steps in order with `executor` and stops at the first one that fails. The stderr of
+/// each step is copied to `log` when it is given.
+pub fn run_steps(
+    executor: &dyn Executor,
+    steps: &[ExpandedStep],
+    mut log: Option<&mut JobLog>,
+    observer: &mut dyn Observer,
+) -> Result<(), Error> {
+    for (idx, step) in steps.iter().enumerate() {
+        observer.step_started(idx, step);
+        let stdout = observer.stdout();
+        let result = executor.execute(step, log.as_deref_mut(), stdout, &mut |line| {
+            observer.stderr_line(step, line);
+            if let Some(progress) = Progress::parse(line) {
+                observer.progress(step, &progress);
+            }
+        });
+        match result {
+            Ok(exit_code) => {
+                observer.step_finished(idx, step, exit_code);
+                if exit_code != Some(0) {
+                    return Err(Error::FfmpegFailed(exit_code));
+                }
+            }
+            Err(error) => {
+                observer.step_finished(idx, step, None);
+                return Err(Error::Other(format!(
+                    "Unable to run {}: {}",
+                    step.argv[0], error
+                )));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::quality::Scores;
+use crate::{get_config_path, CommandOption};
+
+/// A finished job, stored as one line of JSON in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub hash: String,
+    pub title: String,
+    /// The files `{{input_path}}` was run on. A concat job has all the joined files.
+    pub input_paths: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, PathBuf>,
+    /// Flags and values of the options whose conditions held.
+    pub options: Vec<String>,
+    /// The options with their conditions, which a rerun evaluates again for its input. Missing in
+    /// entries written before they were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_options: Option<Vec<CommandOption>>,
+    /// Values of `var.<name>` that the conditions were evaluated with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    pub output_path: PathBuf,
+    /// The argv of each step that was run.
+    pub argv: Vec<Vec<String>>,
+    pub succeeded: bool,
+    pub exit_code: Option<i32>,
+    /// Seconds from the start of the first step to the end of the last one.
+    pub duration: f64,
+    pub output_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<PathBuf>,
+    /// Scores of the output against the input, measured with `--measure-quality`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Scores>,
+}
+
+pub fn history_path() -> PathBuf {
+    get_config_path().with_file_name("history.jsonl")
+}
+
+pub fn append(entry: &HistoryEntry) -> Result<(), String> {
+    let path = history_path();
+    let line = serde_json::to_string(entry).map_err(|error| error.to_string())?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+}
+
+/// Every entry in the history file, oldest first. Lines that cannot be parsed are skipped.
+pub fn load() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_path();
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let history = fs::read_to_string(&path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    Ok(history
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+impl HistoryEntry {
+    /// Whether `query` appears in the title, hash or any path of the entry, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        let paths = self
+            .input_paths
+            .iter()
+            .chain(self.inputs.values())
+            .chain(std::iter::once(&self.output_path));
+        [self.title.as_str(), self.hash.as_str()]
+            .iter()
+            .any(|text| text.to_lowercase().contains(&query))
+            || paths
+                .map(|path| path.display().to_string().to_lowercase())
+                .any(|path| path.contains(&query))
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, IsTerminal, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, INTERRUPTED_EXIT_CODE};
+use crate::{output, report_error};
+
+static CHILD_RUNNING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a Ctrl+C handler that resets the terminal before exiting.
+/// While a child process is running, the signal is left to the child so that ffmpeg can finalize its output.
+pub fn install_handler() {
+    ctrlc::set_handler(|| {
+        if CHILD_RUNNING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            return;
+        }
+        if !output::is_json() {
+            restore_terminal();
+        }
+        report_error(&Error::Interrupted);
+        process::exit(INTERRUPTED_EXIT_CODE);
+    })
+    .expect("failed to set Ctrl+C handler");
+}
+
+/// Marks whether a child process currently owns the terminal.
+pub fn set_child_running(running: bool) {
+    CHILD_RUNNING.store(running, Ordering::SeqCst);
+}
+
+/// Resets colors and moves to a fresh line so the shell prompt is not left in a half-written line.
+pub fn restore_terminal() {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        let _ = write!(stdout, "\x1b[0m");
+    }
+    let _ = writeln!(stdout);
+    let _ = stdout.flush();
+}
+
+/// Whether Ctrl+C was pressed while a child process was running.
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,823 @@
+//! Planning and running a job: a command with its inputs, options and output, expanded into
+//! steps, run with ffmpeg, checked and recorded in the history.
+//!
+//! What happens along the way is reported to an [`Observer`], which is also where a caller asks
+//! the user, such as whether to re-encode inputs that cannot be joined as they are.
+
+use chrono::{Local, SecondsFormat};
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tempfile::TempDir;
+
+use crate::condition::Variables;
+use crate::error::Error;
+use crate::execute::{self, Executor};
+use crate::history::{self, HistoryEntry};
+use crate::job_log::JobLog;
+use crate::probe::{self, ProbeResult};
+use crate::quality::{self, QualityReport};
+use crate::size::{format_size, parse_size};
+use crate::template::{self, ExpandedStep};
+use crate::verify::{self, Check};
+use crate::{concat, encoders, get_hash, Command, CommandOption, Encoder, Verify};
+
+/// Bitrate assumed for an audio stream whose bitrate ffprobe does not report.
+const DEFAULT_AUDIO_BITRATE: u64 = 128_000;
+/// Share of the target size reserved for the container.
+const CONTAINER_OVERHEAD: f64 = 0.02;
+const MIN_VIDEO_BITRATE: u64 = 10_000;
+pub const TARGET_SIZE_ATTEMPTS: usize = 3;
+/// Extra reduction applied on top of the overshoot ratio when retrying.
+const RETRY_BITRATE_MARGIN: f64 = 0.95;
+
+/// Codecs used when joining inputs whose streams differ, since they cannot be stream-copied, for
+/// the streams that the options of the command give no codec.
+const CONCAT_REENCODE_VIDEO_OPTIONS: [&str; 4] = ["-c:v", "libx264", "-crf", "18"];
+const CONCAT_REENCODE_AUDIO_OPTIONS: [&str; 4] = ["-c:a", "aac", "-b:a", "192k"];
+
+/// What a job is asked to do. Every flow of the CLI fills one in, from the arguments, the prompts
+/// or the history, so that commands are expanded and run the same way in all of them.
+#[derive(Debug, Clone)]
+pub struct JobRequest<'a> {
+    pub command: &'a Command,
+    /// Files to run the command on. A concat command joins all of them, and any other command
+    /// takes the first.
+    pub input_paths: Vec<PathBuf>,
+    pub inputs: HashMap<String, PathBuf>,
+    pub options: Vec<CommandOption>,
+    /// Values of `var.<name>` in the conditions.
+    pub vars: BTreeMap<String, String>,
+    pub output_path: PathBuf,
+    pub target_size: Option<u64>,
+}
+
+/// A job whose steps are expanded and ready to run.
+pub struct JobPlan<'a> {
+    command: &'a Command,
+    /// Path given to `{{input_path}}`, which is the generated list file for a concat job.
+    input_path: PathBuf,
+    /// Files the job is recorded to be run on in the history.
+    sources: Vec<PathBuf>,
+    inputs: HashMap<String, PathBuf>,
+    /// The options of the request, before their conditions are evaluated.
+    command_options: Vec<CommandOption>,
+    /// Flags and values of the options whose conditions hold.
+    options: Vec<String>,
+    /// `-c:v` and the options of the chosen encoder, which come before `options`. They are not
+    /// recorded in the history, since a rerun chooses the encoder again.
+    encoder_options: Vec<String>,
+    output_path: PathBuf,
+    /// Holds the files between steps until the job ends.
+    temp_dir: TempDir,
+    variables: Variables,
+    vars: BTreeMap<String, String>,
+    /// Target size and the planned video bitrate.
+    target: Option<(u64, u64)>,
+    steps: Vec<ExpandedStep>,
+}
+
+impl JobPlan<'_> {
+    pub fn steps(&self) -> &[ExpandedStep] {
+        &self.steps
+    }
+}
+
+/// How a job ended.
+pub struct JobResult {
+    pub result: Result<(), Error>,
+    pub log_path: Option<PathBuf>,
+}
+
+/// Receives what happens while a job is planned and run, on top of the steps. Every method does
+/// nothing by default, and `()` ignores everything.
+pub trait Observer: execute::Observer {
+    /// Called with information that is not an event of its own, such as that the inputs of a
+    /// concat job could not be checked.
+    fn message(&mut self, _message: &str) {}
+
+    /// Called when something went wrong that does not fail the job, such as when the history
+    /// could not be written.
+    fn warning(&mut self, _message: &str) {}
+
+    /// Called with the encoder chosen for the command, and the ones before it that ffmpeg does not
+    /// have.
+    fn encoder_chosen(&mut self, _encoder: &Encoder, _unavailable: &[&str]) {}
+
+    /// Called when the streams of the inputs of a concat job differ, with what differs. Returns
+    /// whether to re-encode them to match the first input instead of failing. An error stops the
+    /// job with it.
+    fn accept_reencode(&mut self, _mismatches: &[String]) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Called with the video bitrate planned for a target size, and the duration and the audio
+    /// bitrate it is computed from.
+    fn bitrate_planned(
+        &mut self,
+        _duration: f64,
+        _audio_bitrate: u64,
+        _video_bitrate: u64,
+        _target_size: u64,
+    ) {
+    }
+
+    /// Called before each attempt to fit the output in the target size.
+    fn attempt_started(&mut self, _attempt: usize, _video_bitrate: u64) {}
+
+    /// Called with the size of the output after an attempt.
+    fn attempt_finished(&mut self, _output_size: u64, _target_size: u64) {}
+
+    /// Called before the steps run, which is once per attempt for a target size.
+    fn steps_started(&mut self, _steps: &[ExpandedStep]) {}
+
+    /// Called after the steps ran, unless the job was interrupted.
+    fn steps_finished(&mut self, _result: &Result<(), Error>) {}
+
+    /// Whether the user asked to stop while the steps ran. The job then fails with
+    /// [`Error::Interrupted`], whatever ffmpeg exited with.
+    fn interrupted(&self) -> bool {
+        false
+    }
+
+    /// Called with the checks of the output.
+    fn output_verified(&mut self, _checks: &[Check]) {}
+
+    /// Called with the quality of the output against the input.
+    fn quality_measured(&mut self, _report: &QualityReport) {}
+
+    /// Called when the job ends with a log, and whether it failed.
+    fn log_saved(&mut self, _log: &JobLog, _failed: bool) {}
+
+    /// Called after the job is appended to the history, with the files it was run on and its
+    /// error.
+    fn job_recorded(
+        &mut self,
+        _sources: &[PathBuf],
+        _entry: &HistoryEntry,
+        _error: Option<&Error>,
+    ) {
+    }
+}
+
+impl Observer for () {}
+
+/// What jobs are run with: the tools, the executor, and what is cached between and within jobs.
+pub struct Context {
+    ffmpeg_path: String,
+    ffprobe_path: String,
+    executor: Box<dyn Executor>,
+    log_dir: PathBuf,
+    dry_run: bool,
+    verify: bool,
+    measure_quality: bool,
+    /// Encoders of ffmpeg, listed when a command with `encoders` first runs.
+    available_encoders: OnceCell<Result<HashSet<String>, String>>,
+    /// Results of ffprobe for the files of the current job, so that each file is probed once.
+    probes: RefCell<HashMap<PathBuf, ProbeResult>>,
+}
+
+impl Context {
+    /// A context whose jobs write their logs to `log_dir`.
+    pub fn new(
+        ffmpeg_path: &str,
+        ffprobe_path: &str,
+        executor: Box<dyn Executor>,
+        log_dir: &Path,
+    ) -> Context {
+        Context {
+            ffmpeg_path: ffmpeg_path.to_string(),
+            ffprobe_path: ffprobe_path.to_string(),
+            executor,
+            log_dir: log_dir.to_path_buf(),
+            dry_run: false,
+            verify: false,
+            measure_quality: false,
+            available_encoders: OnceCell::new(),
+            probes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Skips the log, the history and the checks of the output, for an executor that runs
+    /// nothing.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Context {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Checks the output with the basic checks also for commands without `verify`.
+    pub fn with_verify(mut self, verify: bool) -> Context {
+        self.verify = verify;
+        self
+    }
+
+    /// Compares the output with the input after a job succeeds.
+    pub fn with_measure_quality(mut self, measure_quality: bool) -> Context {
+        self.measure_quality = measure_quality;
+        self
+    }
+
+    /// The result of ffprobe for `path`, which is probed only the first time until
+    /// [`Context::clear_probes`].
+    pub fn probe(&self, path: &Path) -> Result<ProbeResult, String> {
+        if let Some(probe_result) = self.probes.borrow().get(path) {
+            return Ok(probe_result.clone());
+        }
+        let probe_result = probe::probe(self.ffprobe_path.as_str(), path)?;
+        self.probes
+            .borrow_mut()
+            .insert(path.to_path_buf(), probe_result.clone());
+        Ok(probe_result)
+    }
+
+    /// Drops the probes of the job that ended, since a watched directory can get a new file of
+    /// the same name.
+    pub fn clear_probes(&self) {
+        self.probes.borrow_mut().clear();
+    }
+
+    /// The first encoder of the command that ffmpeg has. `None` for a command without
+    /// `encoders`.
+    pub fn available_encoder<'a>(
+        &self,
+        command: &'a Command,
+    ) -> Result<Option<&'a Encoder>, Error> {
+        if command.encoders.is_empty() {
+            return Ok(None);
+        }
+        let available = self
+            .available_encoders
+            .get_or_init(|| encoders::list_encoders(self.ffmpeg_path.as_str()))
+            .as_ref()
+            .map_err(|error| Error::Other(error.clone()))?;
+        match encoders::choose(&command.encoders, available) {
+            Some(encoder) => Ok(Some(encoder)),
+            None => Err(Error::Config(format!(
+                "None of the encoders {} is available in ffmpeg.",
+                command
+                    .encoders
+                    .iter()
+                    .map(|encoder| encoder.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    /// Variables for the conditions of the command, with `var.<name>` from `vars` and
+    /// `input.<field>` read from `input_path`. The input is probed only when the command has
+    /// conditions.
+    fn variables(
+        &self,
+        command: &Command,
+        input_path: &Path,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<Variables, Error> {
+        let variables = Variables::new().with_vars(vars);
+        if command.conditions().next().is_none() {
+            return Ok(variables);
+        }
+        let probe_result = self.probe(input_path).map_err(|error| {
+            Error::Validation(format!("Unable to evaluate the conditions: {}", error))
+        })?;
+        Ok(variables.with_input(&probe_result))
+    }
+
+    /// `-c:v` and the options of the first encoder of the command that ffmpeg has, resolved with
+    /// `variables`, after reporting which one was chosen. Empty for a command without `encoders`.
+    fn encoder_options(
+        &self,
+        command: &Command,
+        variables: &Variables,
+        observer: &mut dyn Observer,
+    ) -> Result<Vec<String>, Error> {
+        let Some(encoder) = self.available_encoder(command)? else {
+            return Ok(Vec::new());
+        };
+        let unavailable: Vec<&str> = command
+            .encoders
+            .iter()
+            .map(|encoder| encoder.name.as_str())
+            .take_while(|name| *name != encoder.name)
+            .collect();
+        observer.encoder_chosen(encoder, &unavailable);
+        template::option_args(&encoders::encoder_options(encoder), variables).map_err(Error::Config)
+    }
+
+    /// Computes the video bitrate in bits per second that makes the output of `input_path` fit in
+    /// `target_size` bytes.
+    fn plan_video_bitrate(
+        &self,
+        input_path: &Path,
+        options: &[String],
+        target_size: u64,
+        observer: &mut dyn Observer,
+    ) -> Result<u64, String> {
+        let probe_result = self.probe(input_path)?;
+        let duration = probe_result
+            .duration()
+            .filter(|duration| *duration > 0.0)
+            .ok_or(format!(
+                "Unable to get the duration of {}.",
+                input_path.display()
+            ))?;
+        let audio_bitrate = match options.iter().position(|s| s == "-b:a" || s == "-ab") {
+            Some(idx) => options.get(idx + 1).and_then(|s| parse_size(s)),
+            None => None,
+        }
+        .or(probe_result.audio_bitrate())
+        .unwrap_or(DEFAULT_AUDIO_BITRATE * probe_result.streams_of_type("audio").count() as u64);
+
+        let total_bitrate = target_size as f64 * 8.0 / duration * (1.0 - CONTAINER_OVERHEAD);
+        let video_bitrate = total_bitrate - audio_bitrate as f64;
+        observer.bitrate_planned(
+            duration,
+            audio_bitrate,
+            video_bitrate.max(0.0) as u64,
+            target_size,
+        );
+        if video_bitrate < MIN_VIDEO_BITRATE as f64 {
+            return Err(format!(
+                "{} is too small for a video of {:.1}s.",
+                format_size(target_size),
+                duration
+            ));
+        }
+        Ok(video_bitrate as u64)
+    }
+
+    /// Probes every input of a concat command. Returns `None` when ffprobe fails, in which case
+    /// the inputs are joined unchecked.
+    fn probe_concat_inputs(
+        &self,
+        input_paths: &[PathBuf],
+        observer: &mut dyn Observer,
+    ) -> Option<Vec<(PathBuf, ProbeResult)>> {
+        let mut probes = Vec::new();
+        for input_path in input_paths {
+            match self.probe(input_path) {
+                Ok(probe_result) => probes.push((input_path.clone(), probe_result)),
+                Err(error) => {
+                    observer.message(
+                        format!(
+                            "Unable to check the stream parameters, so the files are joined as they are: {}",
+                            error
+                        )
+                        .as_str(),
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(probes)
+    }
+
+    /// Joins the inputs with the concat demuxer through a generated list file, or with the concat
+    /// filter when their streams differ and the observer accepts re-encoding.
+    fn plan_concat<'a>(
+        &self,
+        request: JobRequest<'a>,
+        observer: &mut dyn Observer,
+    ) -> Result<JobPlan<'a>, Error> {
+        let input_paths = &request.input_paths;
+        if input_paths.len() < 2 {
+            return Err(Error::Validation(
+                "At least two files are needed to concatenate.".to_string(),
+            ));
+        }
+        let temp_dir = create_temp_dir()?;
+        let variables = self.variables(request.command, &input_paths[0], &request.vars)?;
+        let options = template::option_args(&request.options, &variables).map_err(Error::Config)?;
+
+        if let Some(probes) = self.probe_concat_inputs(input_paths, observer) {
+            let mismatches = concat::find_mismatches(&probes);
+            if !mismatches.is_empty() {
+                if !observer.accept_reencode(&mismatches)? {
+                    return Err(Error::Validation(
+                        "Stream parameters of the files differ.".to_string(),
+                    ));
+                }
+                let encoder_options =
+                    self.encoder_options(request.command, &variables, observer)?;
+                let options = concat_reencode_options(&encoder_options, &options);
+                let mut argv = vec![self.ffmpeg_path.clone()];
+                argv.extend(concat::reencode_args(&probes));
+                argv.extend(encoder_options.iter().chain(options.iter()).cloned());
+                argv.push(request.output_path.display().to_string());
+                return Ok(JobPlan {
+                    command: request.command,
+                    input_path: input_paths[0].clone(),
+                    sources: request.input_paths.clone(),
+                    inputs: request.inputs,
+                    command_options: request.options,
+                    options,
+                    encoder_options,
+                    output_path: request.output_path,
+                    temp_dir,
+                    variables,
+                    vars: request.vars,
+                    target: None,
+                    steps: vec![ExpandedStep {
+                        name: "reencode".to_string(),
+                        argv,
+                    }],
+                });
+            }
+        }
+
+        let list_path = temp_dir.path().join("concat.txt");
+        concat::write_list(input_paths, &list_path)
+            .map_err(|error| Error::Other(format!("Unable to write the concat list: {}", error)))?;
+        let encoder_options = self.encoder_options(request.command, &variables, observer)?;
+        let steps = template::expand_steps(
+            request.command,
+            &template::StepValues {
+                ffmpeg_path: self.ffmpeg_path.as_str(),
+                input_path: &list_path,
+                inputs: &request.inputs,
+                options: &[encoder_options.as_slice(), &options].concat(),
+                output_path: &request.output_path,
+                temp_dir: temp_dir.path(),
+            },
+            &variables,
+        )
+        .map_err(Error::Config)?;
+        Ok(JobPlan {
+            command: request.command,
+            input_path: list_path,
+            sources: request.input_paths,
+            inputs: request.inputs,
+            command_options: request.options,
+            options,
+            encoder_options,
+            output_path: request.output_path,
+            temp_dir,
+            variables,
+            vars: request.vars,
+            target: None,
+            steps,
+        })
+    }
+
+    /// Runs the steps in order and stops at the first one that fails. The stderr of each step is
+    /// also written to `log` when it is given.
+    fn run_steps(
+        &self,
+        steps: &[ExpandedStep],
+        log: &mut Option<JobLog>,
+        observer: &mut dyn Observer,
+    ) -> Result<(), Error> {
+        observer.steps_started(steps);
+        let result = execute::run_steps(self.executor.as_ref(), steps, log.as_mut(), observer);
+        // Ctrl+C is left to ffmpeg while it runs, so the job stops here instead, whatever ffmpeg
+        // exited with. Every attempt for a target size runs through here.
+        if observer.interrupted() {
+            return Err(Error::Interrupted);
+        }
+        observer.steps_finished(&result);
+        result
+    }
+
+    /// Runs the two-pass encode and retries with a lower bitrate while the output is larger than
+    /// `target_size`.
+    fn run_target_size(
+        &self,
+        plan: &mut JobPlan,
+        target_size: u64,
+        video_bitrate: u64,
+        log: &mut Option<JobLog>,
+        observer: &mut dyn Observer,
+    ) -> Result<(), Error> {
+        let output_path = plan.output_path.clone();
+        let output_path = output_path.as_path();
+        let mut video_bitrate = video_bitrate;
+        for attempt in 1..=TARGET_SIZE_ATTEMPTS {
+            if attempt > 1 {
+                plan.steps = template::expand_steps(
+                    plan.command,
+                    &template::StepValues {
+                        ffmpeg_path: self.ffmpeg_path.as_str(),
+                        input_path: &plan.input_path,
+                        inputs: &plan.inputs,
+                        options: &with_video_bitrate(
+                            &[plan.encoder_options.as_slice(), &plan.options].concat(),
+                            video_bitrate,
+                        ),
+                        output_path,
+                        temp_dir: plan.temp_dir.path(),
+                    },
+                    &plan.variables,
+                )
+                .map_err(Error::Config)?;
+            }
+            observer.attempt_started(attempt, video_bitrate);
+            self.run_steps(&plan.steps, log, observer)?;
+
+            let output_size = fs::metadata(output_path)
+                .map_err(|error| {
+                    Error::Other(format!(
+                        "Unable to read {}: {}",
+                        output_path.display(),
+                        error
+                    ))
+                })?
+                .len();
+            observer.attempt_finished(output_size, target_size);
+            if output_size <= target_size {
+                return Ok(());
+            }
+            video_bitrate = (video_bitrate as f64 * target_size as f64 / output_size as f64
+                * RETRY_BITRATE_MARGIN) as u64;
+            // Remove the oversized output so that ffmpeg does not ask whether to overwrite it.
+            if attempt < TARGET_SIZE_ATTEMPTS {
+                fs::remove_file(output_path)?;
+            }
+        }
+        Err(Error::Other(format!(
+            "Unable to fit the output in {} after {} attempts.",
+            format_size(target_size),
+            TARGET_SIZE_ATTEMPTS
+        )))
+    }
+
+    /// Checks the output by `verify` of the command, or by the basic checks when the context
+    /// verifies every output.
+    fn verify_output(&self, plan: &JobPlan, observer: &mut dyn Observer) -> Result<(), Error> {
+        let spec = match &plan.command.verify {
+            Some(spec) => spec.clone(),
+            None if self.verify => Verify::default(),
+            None => return Ok(()),
+        };
+        let checks = verify::verify_output(
+            &spec,
+            self.ffprobe_path.as_str(),
+            &|source| self.probe(source),
+            &plan.sources,
+            &plan.output_path,
+        );
+        observer.output_verified(&checks);
+        let problems: Vec<&str> = checks
+            .iter()
+            .filter(|check| !check.ok)
+            .map(|check| check.message.as_str())
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::VerificationFailed(problems.join(" ")))
+        }
+    }
+
+    /// Compares the output of the job with its input when the context measures the quality. A
+    /// failure to measure is only a warning, since the job itself succeeded.
+    fn measure_quality(
+        &self,
+        plan: &JobPlan,
+        observer: &mut dyn Observer,
+    ) -> Option<quality::Scores> {
+        if !self.measure_quality {
+            return None;
+        }
+        let [reference] = plan.sources.as_slice() else {
+            observer.message("Quality is not measured for a job with several input files.");
+            return None;
+        };
+        observer.message("Measuring the quality of the output.");
+        match quality::measure(
+            self.ffmpeg_path.as_str(),
+            self.ffprobe_path.as_str(),
+            reference,
+            &plan.output_path,
+        ) {
+            Ok(report) => {
+                observer.quality_measured(&report);
+                Some(report.scores)
+            }
+            Err(error) => {
+                observer.warning(format!("Unable to measure the quality: {}", error).as_str());
+                None
+            }
+        }
+    }
+}
+
+/// Expands the steps of the requested job, after planning the video bitrate when a target size is
+/// given. For a concat command, the inputs are checked to be joinable, and the observer is asked
+/// whether to re-encode them when they are not.
+pub fn plan<'a>(
+    context: &Context,
+    request: JobRequest<'a>,
+    observer: &mut dyn Observer,
+) -> Result<JobPlan<'a>, Error> {
+    if request.command.concat {
+        return context.plan_concat(request, observer);
+    }
+    let temp_dir = create_temp_dir()?;
+    let input_path = request.input_paths[0].clone();
+    let variables = context.variables(request.command, &input_path, &request.vars)?;
+    let options = template::option_args(&request.options, &variables).map_err(Error::Config)?;
+    let encoder_options = context.encoder_options(request.command, &variables, observer)?;
+    let all_options = [encoder_options.as_slice(), &options].concat();
+    let video_bitrate = match request.target_size {
+        Some(target_size) => Some(
+            context
+                .plan_video_bitrate(&input_path, &all_options, target_size, observer)
+                .map_err(Error::Other)?,
+        ),
+        None => None,
+    };
+    let steps = template::expand_steps(
+        request.command,
+        &template::StepValues {
+            ffmpeg_path: context.ffmpeg_path.as_str(),
+            input_path: &input_path,
+            inputs: &request.inputs,
+            options: &video_bitrate.map_or(all_options.clone(), |video_bitrate| {
+                with_video_bitrate(&all_options, video_bitrate)
+            }),
+            output_path: &request.output_path,
+            temp_dir: temp_dir.path(),
+        },
+        &variables,
+    )
+    .map_err(Error::Config)?;
+    Ok(JobPlan {
+        command: request.command,
+        sources: vec![input_path.clone()],
+        input_path,
+        inputs: request.inputs,
+        command_options: request.options,
+        options,
+        encoder_options,
+        output_path: request.output_path,
+        temp_dir,
+        variables,
+        vars: request.vars,
+        target: request.target_size.zip(video_bitrate),
+        steps,
+    })
+}
+
+/// Runs the steps of the plan, retrying for the target size when the plan has one, checks the
+/// output and appends the job to the history.
+pub fn run(context: &Context, mut plan: JobPlan, observer: &mut dyn Observer) -> JobResult {
+    if context.dry_run {
+        return JobResult {
+            result: context.run_steps(&plan.steps, &mut None, observer),
+            log_path: None,
+        };
+    }
+    let hash = get_hash(plan.command.title.clone());
+    let mut log = match JobLog::create(
+        &context.log_dir,
+        hash.as_str(),
+        plan.sources.first().unwrap_or(&plan.input_path),
+    ) {
+        Ok(log) => Some(log),
+        Err(error) => {
+            observer.warning(format!("Unable to create a log file: {}", error).as_str());
+            None
+        }
+    };
+    let started = Instant::now();
+    let result = match plan.target {
+        Some((target_size, video_bitrate)) => {
+            context.run_target_size(&mut plan, target_size, video_bitrate, &mut log, observer)
+        }
+        None => context.run_steps(&plan.steps, &mut log, observer),
+    };
+    let result = result.and_then(|()| context.verify_output(&plan, observer));
+    let quality = match result {
+        Ok(()) => context.measure_quality(&plan, observer),
+        Err(_) => None,
+    };
+    let log_path = log.as_ref().map(|log| log.path().to_path_buf());
+    if let Some(log) = &log {
+        observer.log_saved(log, result.is_err());
+    }
+    let entry = HistoryEntry {
+        timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        hash,
+        title: plan.command.title.clone(),
+        input_paths: plan.sources.clone(),
+        inputs: plan
+            .inputs
+            .iter()
+            .map(|(name, path)| (name.clone(), path.clone()))
+            .collect(),
+        options: plan.options.clone(),
+        command_options: Some(plan.command_options.clone()),
+        vars: plan.vars.clone(),
+        output_path: plan.output_path.clone(),
+        argv: plan.steps.iter().map(|step| step.argv.clone()).collect(),
+        succeeded: result.is_ok(),
+        exit_code: match &result {
+            Ok(()) => Some(0),
+            Err(error) => error.ffmpeg_exit_code(),
+        },
+        duration: started.elapsed().as_secs_f64(),
+        output_size: fs::metadata(&plan.output_path)
+            .ok()
+            .map(|metadata| metadata.len()),
+        log_path: log_path.clone(),
+        quality,
+    };
+    record(&plan.sources, &entry, result.as_ref().err(), observer);
+    JobResult { result, log_path }
+}
+
+/// Records a job that failed before it ran, such as when its conditions could not be evaluated,
+/// so that it is in the history like the jobs that ran.
+pub fn record_failure(request: &JobRequest, error: &Error, observer: &mut dyn Observer) {
+    let entry = HistoryEntry {
+        timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        hash: get_hash(request.command.title.clone()),
+        title: request.command.title.clone(),
+        input_paths: request.input_paths.clone(),
+        inputs: request
+            .inputs
+            .iter()
+            .map(|(name, path)| (name.clone(), path.clone()))
+            .collect(),
+        options: Vec::new(),
+        command_options: Some(request.options.clone()),
+        vars: request.vars.clone(),
+        output_path: request.output_path.clone(),
+        argv: Vec::new(),
+        succeeded: false,
+        exit_code: None,
+        duration: 0.0,
+        output_size: None,
+        log_path: None,
+        quality: None,
+    };
+    record(&request.input_paths, &entry, Some(error), observer);
+}
+
+/// Appends the job to the history and passes it on to the observer.
+fn record(
+    sources: &[PathBuf],
+    entry: &HistoryEntry,
+    error: Option<&Error>,
+    observer: &mut dyn Observer,
+) {
+    if let Err(error) = history::append(entry) {
+        observer.warning(error.as_str());
+    }
+    observer.job_recorded(sources, entry, error);
+}
+
+fn create_temp_dir() -> Result<TempDir, Error> {
+    tempfile::Builder::new()
+        .prefix("kffmpeg-")
+        .tempdir()
+        .map_err(|error| Error::Other(format!("Unable to create a temporary directory: {}", error)))
+}
+
+/// `options` of a concat command for re-encoding its inputs after `encoder_options`. Stream copy
+/// is dropped, since it cannot be used with the concat filter, and the codecs in
+/// [`CONCAT_REENCODE_VIDEO_OPTIONS`] and [`CONCAT_REENCODE_AUDIO_OPTIONS`] are added for the streams
+/// that have none.
+fn concat_reencode_options(encoder_options: &[String], options: &[String]) -> Vec<String> {
+    let mut options: Vec<String> = options
+        .chunks(2)
+        .filter(|option| !(encoders::is_codec_flag(&option[0], None) && option[1] == "copy"))
+        .flatten()
+        .cloned()
+        .collect();
+    for (stream, defaults) in [
+        ('v', CONCAT_REENCODE_VIDEO_OPTIONS),
+        ('a', CONCAT_REENCODE_AUDIO_OPTIONS),
+    ] {
+        let has_codec = encoder_options
+            .iter()
+            .chain(options.iter())
+            .any(|s| encoders::is_codec_flag(s, Some(stream)));
+        if !has_codec {
+            options.extend(defaults.iter().map(|s| s.to_string()));
+        }
+    }
+    options
+}
+
+/// Flags of quality-based rate control, which would override the bitrate for a target size.
+const QUALITY_FLAGS: [&str; 3] = ["-crf", "-cq", "-global_quality"];
+
+/// `options` with `-b:v` set to `video_bitrate`, replacing an existing value, and without the
+/// flags in [`QUALITY_FLAGS`] and their values.
+fn with_video_bitrate(options: &[String], video_bitrate: u64) -> Vec<String> {
+    let value = format!("{}k", video_bitrate / 1000);
+    let mut options: Vec<String> = options
+        .chunks(2)
+        .filter(|option| !QUALITY_FLAGS.contains(&option[0].as_str()))
+        .flatten()
+        .cloned()
+        .collect();
+    match options.iter().position(|s| s == "-b:v") {
+        Some(idx) if idx + 1 < options.len() => options[idx + 1] = value,
+        _ => options.extend(["-b:v".to_string(), value]),
+    }
+    options
+}
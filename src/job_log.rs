@@ -0,0 +1,92 @@
+use chrono::Local;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command as ProcessCommand, ExitStatus, Stdio};
+
+/// Log file of a job. The stderr of every step is written both to the terminal and to the file.
+pub struct JobLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl JobLog {
+    /// Creates a log file in `dir` named after the time, the command's hash and the input file.
+    pub fn create(dir: &Path, hash: &str, input_path: &Path) -> io::Result<JobLog> {
+        fs::create_dir_all(dir)?;
+        let stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = format!("{}_{}_{}", Local::now().format("%Y%m%d-%H%M%S"), hash, stem);
+        let mut path = dir.join(format!("{}.log", name));
+        let mut idx = 1;
+        while path.exists() {
+            path = dir.join(format!("{}_{}.log", name, idx));
+            idx += 1;
+        }
+        let file = File::create(&path)?;
+        Ok(JobLog { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs `command` with its stderr copied to the terminal and to the log, after a header line
+    /// that names the step and its argv. `on_line` is called with each line of stderr, where the
+    /// progress updates that ffmpeg ends with `\r` also count as lines.
+    pub fn run(
+        &mut self,
+        step_name: &str,
+        argv: &[String],
+        command: &mut ProcessCommand,
+        on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<ExitStatus> {
+        writeln!(self.file, "==> {}: {}", step_name, argv.join(" "))?;
+        let mut child = command.stderr(Stdio::piped()).spawn()?;
+        let mut stderr = child.stderr.take().unwrap();
+        let mut buffer = [0; 8192];
+        let mut line = Vec::new();
+        loop {
+            let len = match stderr.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            // The terminal may be gone, but the log should still be complete.
+            let _ = io::stderr().write_all(&buffer[..len]);
+            self.file.write_all(&buffer[..len])?;
+            for byte in &buffer[..len] {
+                if *byte == b'\n' || *byte == b'\r' {
+                    on_line(String::from_utf8_lossy(&line).as_ref());
+                    line.clear();
+                } else {
+                    line.push(*byte);
+                }
+            }
+        }
+        if !line.is_empty() {
+            on_line(String::from_utf8_lossy(&line).as_ref());
+        }
+        let status = child.wait()?;
+        writeln!(self.file)?;
+        Ok(status)
+    }
+
+    /// The last `count` lines of the log. ffmpeg rewrites its progress line with `\r`, so each
+    /// progress update counts as a line.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let log = match fs::read(&self.path) {
+            Ok(log) => String::from_utf8_lossy(&log).to_string(),
+            Err(_) => return Vec::new(),
+        };
+        let lines: Vec<&str> = log
+            .split(['\n', '\r'])
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect();
+        lines[lines.len().saturating_sub(count)..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
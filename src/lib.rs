@@ -0,0 +1,469 @@
+//! The recipe engine of kffmpeg: the config model, template expansion, output paths and
+//! running the expanded steps with ffmpeg.
+//!
+//! ```no_run
+//! use std::collections::HashMap;
+//! use std::path::Path;
+//!
+//! let config = kffmpeg::Config::load(&kffmpeg::get_config_path())?;
+//! let command = config.find_command("3966fc31").unwrap();
+//! let input_path = Path::new("movie.mp4");
+//! let output_path = command.default_output_path(input_path)?;
+//! // Commands with `when:` conditions also need `Variables::with_input` and ffprobe.
+//! let variables = kffmpeg::condition::Variables::new().with_vars(&command.variables);
+//! let options = kffmpeg::template::option_args(&command.options, &variables)
+//!     .map_err(kffmpeg::Error::Config)?;
+//! let temp_dir = tempfile::tempdir()?;
+//! let values = kffmpeg::template::StepValues {
+//!     ffmpeg_path: "ffmpeg",
+//!     input_path,
+//!     inputs: &HashMap::new(),
+//!     options: &options,
+//!     output_path: &output_path,
+//!     temp_dir: temp_dir.path(),
+//! };
+//! let steps = kffmpeg::template::expand_steps(command, &values, &variables)
+//! .map_err(kffmpeg::Error::Config)?;
+//! kffmpeg::execute::run_steps(&kffmpeg::execute::ProcessExecutor, &steps, None, &mut ())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use data_encoding::HEXLOWER;
+use ring::digest;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod concat;
+pub mod condition;
+pub mod config_editor;
+pub mod encoders;
+pub mod error;
+pub mod execute;
+pub mod history;
+pub mod job;
+pub mod job_log;
+pub mod paths;
+pub mod probe;
+pub mod quality;
+pub mod report;
+pub mod size;
+pub mod template;
+pub mod verify;
+
+pub use error::Error;
+
+/// An option of a command, such as `-crf 23`. The flag and the value can be edited before running.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CommandOption {
+    pub flag: String,
+    pub value: String,
+    /// Condition such as `input.height > 720`. The option is used only when it holds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+/// A candidate of the video encoder, with the options that go with it, such as `-cq` for
+/// `h264_nvenc` and `-crf` for `libx264`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Encoder {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<CommandOption>,
+}
+
+/// A recipe in the config file. Its hash is computed from the title with [`get_hash`].
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Command {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub options: Vec<CommandOption>,
+    /// Candidates of the video encoder in the order of preference. The first one that the local
+    /// ffmpeg has is passed as `-c:v` with its options, before `options`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encoders: Vec<Encoder>,
+    pub output_extension: String,
+    /// Appended to the file stem of the input to name the output.
+    pub output_filename_suffix: String,
+    /// Template of the argv. Either this or `steps` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<TemplateEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+    /// Runs `command` twice as the two passes of an encode.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub two_pass: bool,
+    /// Size such as `25M` that the output must fit in. Implies `two_pass`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_size: Option<String>,
+    /// Joins several files, given as `{{input_path}}` in a concat list.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub concat: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Input>,
+    /// Extensions accepted for the input of a command without `inputs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Stream types that the input of a command without `inputs` must have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Default values of `var.<name>` in conditions, which `--var` overrides.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+    /// Checks of the output after ffmpeg succeeds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<Verify>,
+}
+
+/// Checks of the output of a command. The output must always exist, be non-empty and be readable
+/// by ffprobe, and the fields add more checks.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Verify {
+    /// Largest allowed difference in seconds between the durations of the input and the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_tolerance: Option<f64>,
+    /// Stream types that the output must have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams: Vec<String>,
+    /// Largest allowed ratio of the output size to the input size, e.g. `1.0` to reject an output
+    /// that is larger than the input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_ratio: Option<f64>,
+}
+
+impl Command {
+    /// The input that `{{input_path}}` refers to. `extensions` and `requires` of a command
+    /// without named inputs apply to it.
+    pub fn first_input(&self) -> Input {
+        match self.inputs.first() {
+            Some(input) => input.clone(),
+            None => Input {
+                name: "input".to_string(),
+                prompt: Some("Input the path of the video file.".to_string()),
+                extensions: self.extensions.clone(),
+                requires: self.requires.clone(),
+            },
+        }
+    }
+
+    /// Conditions of the options and of the template entries of all steps.
+    pub fn conditions(&self) -> impl Iterator<Item = &str> {
+        let entries = self
+            .command
+            .iter()
+            .chain(self.steps.iter().flat_map(|step| step.command.iter()));
+        self.options
+            .iter()
+            .chain(
+                self.encoders
+                    .iter()
+                    .flat_map(|encoder| encoder.options.iter()),
+            )
+            .filter_map(|option| option.when.as_deref())
+            .chain(entries.filter_map(TemplateEntry::condition))
+    }
+
+    /// `<input file stem><output_filename_suffix><output_extension>` next to the input. A path
+    /// without a name of its own, such as `.`, is resolved to the directory it refers to first.
+    pub fn default_output_path(&self, input_path: &Path) -> Result<PathBuf, Error> {
+        let input_path = match input_path.file_stem() {
+            Some(_) => input_path.to_path_buf(),
+            None => input_path.canonicalize().map_err(|error| {
+                Error::Validation(format!(
+                    "Unable to resolve {}: {}",
+                    input_path.display(),
+                    error
+                ))
+            })?,
+        };
+        let (Some(parent), Some(stem)) = (input_path.parent(), input_path.file_stem()) else {
+            return Err(Error::Validation(format!(
+                "{} has no name to name the output after.",
+                input_path.display()
+            )));
+        };
+        let mut file_name = stem.to_os_string();
+        file_name.push(self.output_filename_suffix.as_str());
+        file_name.push(self.output_extension.as_str());
+        Ok(parent.join(file_name))
+    }
+}
+
+/// Stream types that `requires` accepts, as reported by ffprobe.
+pub const STREAM_TYPES: [&str; 5] = ["video", "audio", "subtitle", "data", "attachment"];
+
+/// A named input file of a command, referred to as `{{inputs.<name>}}`.
+/// The first input is also `{{input_path}}` and names the output file.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(from = "InputDef")]
+pub struct Input {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+}
+
+/// An input can be written as just its name.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InputDef {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        prompt: Option<String>,
+        #[serde(default)]
+        extensions: Vec<String>,
+        #[serde(default)]
+        requires: Vec<String>,
+    },
+}
+
+impl From<InputDef> for Input {
+    fn from(def: InputDef) -> Self {
+        match def {
+            InputDef::Name(name) => Input {
+                name,
+                prompt: None,
+                extensions: Vec::new(),
+                requires: Vec::new(),
+            },
+            InputDef::Full {
+                name,
+                prompt,
+                extensions,
+                requires,
+            } => Input {
+                name,
+                prompt,
+                extensions,
+                requires,
+            },
+        }
+    }
+}
+
+impl Input {
+    /// Whether `path` has one of the accepted extensions. Any file is accepted when none are listed.
+    pub fn accepts(&self, path: &Path) -> bool {
+        let extension = match path.extension() {
+            Some(extension) => extension.to_string_lossy().to_lowercase(),
+            None => return self.extensions.is_empty(),
+        };
+        self.extensions.is_empty()
+            || self
+                .extensions
+                .iter()
+                .any(|accepted| accepted.trim_start_matches('.').to_lowercase() == extension)
+    }
+
+    /// Stream types in `requires` that `probe_result` does not have.
+    pub fn missing_streams(&self, probe_result: &probe::ProbeResult) -> Vec<String> {
+        self.requires
+            .iter()
+            .filter(|codec_type| probe_result.streams_of_type(codec_type).next().is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// The message shown when asking for the path of this input.
+    pub fn prompt(&self) -> String {
+        match &self.prompt {
+            Some(prompt) => prompt.clone(),
+            None => format!("Input the path of {}.", self.name),
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// An element of a command template. Besides a plain argument, it can be a group of arguments
+/// that is used only when its condition holds:
+///
+/// ```yaml
+/// - when: input.height > 720
+///   args: [-vf, "scale=-2:720"]
+/// ```
+#[derive(Serialize, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum TemplateEntry {
+    Arg(String),
+    Conditional { when: String, args: Vec<String> },
+}
+
+impl TemplateEntry {
+    /// The arguments of the entry, whether or not its condition holds.
+    pub fn args(&self) -> &[String] {
+        match self {
+            TemplateEntry::Arg(arg) => std::slice::from_ref(arg),
+            TemplateEntry::Conditional { args, .. } => args,
+        }
+    }
+
+    pub fn condition(&self) -> Option<&str> {
+        match self {
+            TemplateEntry::Arg(_) => None,
+            TemplateEntry::Conditional { when, .. } => Some(when),
+        }
+    }
+
+    /// Whether the entry is the plain argument `arg`.
+    pub fn is_arg(&self, arg: &str) -> bool {
+        matches!(self, TemplateEntry::Arg(s) if s == arg)
+    }
+}
+
+// Written by hand rather than with `untagged`, which would reject unquoted numbers such as the
+// `0` of `-safe, 0`.
+impl<'de> Deserialize<'de> for TemplateEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Conditional {
+            when: String,
+            args: Vec<String>,
+        }
+
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = TemplateEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an argument, or a map with when and args")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<TemplateEntry, E> {
+                Ok(TemplateEntry::Arg(value.to_string()))
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<TemplateEntry, E> {
+                Ok(TemplateEntry::Arg(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<TemplateEntry, E> {
+                Ok(TemplateEntry::Arg(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<TemplateEntry, E> {
+                Ok(TemplateEntry::Arg(value.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<TemplateEntry, E> {
+                Ok(TemplateEntry::Arg(value.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<TemplateEntry, A::Error> {
+                let Conditional { when, args } =
+                    Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(TemplateEntry::Conditional { when, args })
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+/// One ffmpeg invocation of a multi-step command.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Step {
+    pub name: String,
+    pub command: Vec<TemplateEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_extension: Option<String>,
+}
+
+/// The config file, `~/.config/kffmpeg/config.yaml` by default.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Config {
+    pub ffmpeg_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ffprobe_path: Option<String>,
+    /// Directory for the log of each job. Defaults to `logs` next to the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_dir: Option<String>,
+    /// Number of lines of the log shown when a job fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_tail_lines: Option<usize>,
+    pub commands: Vec<Command>,
+}
+
+impl Config {
+    /// Reads and parses the config file at `path`.
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|config_str| {
+                serde_yaml::from_str(&config_str).map_err(|error| error.to_string())
+            })
+            .map_err(|error| Error::Config(format!("Unable to load {}: {}", path.display(), error)))
+    }
+
+    /// The command whose hash is `hash`.
+    pub fn find_command(&self, hash: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|command| get_hash(command.title.clone()) == hash)
+    }
+
+    /// The ffprobe to use. Unless `ffprobe_path` is set, it is the one next to `ffmpeg_path` when
+    /// `should_use_ffmpeg_path_field` is true, and the one on PATH otherwise.
+    pub fn ffprobe_path(&self, should_use_ffmpeg_path_field: bool) -> String {
+        match &self.ffprobe_path {
+            Some(path) => path.clone(),
+            None if should_use_ffmpeg_path_field => probe::ffprobe_path_for(&self.ffmpeg_path),
+            None => "ffprobe".to_string(),
+        }
+    }
+
+    /// Commands that have all of `tags`. Every command is returned when `tags` is empty.
+    pub fn commands_with_tags(&self, tags: &[String]) -> Vec<&Command> {
+        self.commands
+            .iter()
+            .filter(|command| tags.iter().all(|tag| command.tags.contains(tag)))
+            .collect()
+    }
+}
+
+/// Groups commands for display. Ungrouped commands come first, followed by each group
+/// in the order it first appears in the config.
+pub fn group_commands<'a>(commands: &[&'a Command]) -> Vec<(Option<&'a str>, Vec<&'a Command>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&Command>)> = vec![(None, Vec::new())];
+    for command in commands {
+        let group = command.group.as_deref();
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, members)) => members.push(command),
+            None => groups.push((group, vec![command])),
+        }
+    }
+    groups.retain(|(_, members)| !members.is_empty());
+    groups
+}
+
+/// The first 8 hex digits of the SHA-256 of `input`, which identify a command by its title.
+pub fn get_hash(input: String) -> String {
+    let digest = digest::digest(&digest::SHA256, input.as_bytes());
+    let hash = HEXLOWER.encode(digest.as_ref());
+    hash[0..8].to_string()
+}
+
+/// `~/.config/kffmpeg/config.yaml`. The history and the logs are kept next to it.
+pub fn get_config_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap()
+        .join(".config")
+        .join("kffmpeg")
+        .join("config.yaml")
+}
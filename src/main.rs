@@ -1,10 +1,20 @@
 use clap::Parser;
-use serde::{Deserialize, Serialize};
+use colored::Colorize;
+use kffmpeg::{
+    concat, config_editor, error, get_config_path, get_hash, group_commands, history, job_log,
+    probe, Command, CommandOption, Config, Input, STREAM_TYPES,
+};
+use serde_json::json;
 use std::env;
+use std::path::PathBuf;
+mod interrupt;
+#[macro_use]
+mod output;
+mod path_input;
+mod picker;
 mod runner;
 mod startup_checker;
-use data_encoding::HEXLOWER;
-use ring::digest;
+mod watcher;
 
 #[derive(Parser, Debug)]
 #[clap(name = env!("CARGO_PKG_NAME"), version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"), about = env!("CARGO_PKG_DESCRIPTION"))]
@@ -20,52 +30,191 @@ pub struct Args {
 
     #[arg(
         long,
-        help = "Input path to select a command. If this option is specified, the command will be executed without user interaction. You are also need to set --hash. This option is useful when you want to use kffmpeg in a script."
+        help = "Input path to select a command. If this option is specified, the command will be executed without user interaction. You are also need to set --hash. This option is useful when you want to use kffmpeg in a script. For a concat command, specify a directory that contains the files to join."
     )]
     input_path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Show only commands that have this tag. Can be specified multiple times to narrow down the commands to those that have all of the tags."
+    )]
+    tag: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Target size of the output file, e.g. 25M. Overrides target_size of the command. Only commands that have target_size use it."
+    )]
+    target_size: Option<String>,
+
+    #[arg(
+        long = "input",
+        global = true,
+        value_name = "NAME=PATH",
+        help = "Path of a named input of the command, e.g. --input audio=bgm.wav. Can be specified multiple times. The first input can also be given with --input_path."
+    )]
+    inputs: Vec<String>,
+
+    #[arg(
+        long = "var",
+        global = true,
+        value_name = "NAME=VALUE",
+        help = "Value of var.<name> in the when conditions of the command, e.g. --var audio=copy. Can be specified multiple times."
+    )]
+    vars: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        help = "Format of the output. json prints events as JSON lines on stdout for scripts, and needs --hash or a subcommand."
+    )]
+    output_format: OutputFormat,
+
+    #[arg(
+        long,
+        help = "Show the ffmpeg commands that would run without running them. No log or history is written."
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Check that the output exists, is not empty and can be read by ffprobe after ffmpeg succeeds, also for commands without verify."
+    )]
+    verify: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Measure PSNR and SSIM of the output against the input after the command succeeds, and VMAF when ffmpeg has libvmaf."
+    )]
+    measure_quality: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Write a summary of the jobs to this file, updated after each job. The format is CSV, JSON or a Markdown table, chosen by the extension: .csv, .json or .md."
+    )]
+    report: Option<PathBuf>,
+
+    #[command(subcommand)]
+    subcommand: Option<Subcommand>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct CommandOption {
-    flag: String,
-    value: String,
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-struct Command {
-    title: String,
-    options: Vec<CommandOption>,
-    output_extension: String,
-    output_filename_suffix: String,
-    command: Vec<String>,
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Watch a directory and run a command on every file put in it, until interrupted.
+    /// Processed files are moved into done/ or failed/ in the directory.
+    Watch {
+        /// Directory to watch
+        dir: PathBuf,
+
+        #[arg(long, value_name = "HASH", help = "Hash code of the command to run")]
+        command: String,
+
+        #[arg(
+            long,
+            help = "Directory to write the outputs to. Defaults to output/ in the watched directory."
+        )]
+        output_dir: Option<PathBuf>,
+    },
+    /// List past jobs, newest last. Each job is numbered for rerun.
+    History {
+        /// Show only jobs whose title, hash or paths contain this text
+        query: Option<String>,
+
+        #[arg(long, default_value_t = 20, help = "Number of jobs to show")]
+        limit: usize,
+    },
+    /// Run a past job again with the same command, options and paths.
+    Rerun {
+        /// Number of the job shown by history
+        number: usize,
+
+        /// Input to run the job on instead of the original one. For a concat job, a directory.
+        input: Option<PathBuf>,
+    },
+    /// Measure PSNR and SSIM of a video against a reference, and VMAF when ffmpeg has libvmaf.
+    /// The video is scaled to the resolution of the reference.
+    Compare {
+        /// Original video
+        reference: PathBuf,
+
+        /// Encoded video to compare with the reference
+        distorted: PathBuf,
+    },
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-pub struct Config {
-    ffmpeg_path: String,
-    commands: Vec<Command>,
+impl Args {
+    /// `--input` flags split into names and paths. Flags without `=` are returned as errors.
+    fn named_inputs(&self) -> Vec<Result<(String, PathBuf), String>> {
+        self.inputs
+            .iter()
+            .map(|input| match input.split_once('=') {
+                Some((name, path)) => Ok((name.to_string(), PathBuf::from(path))),
+                None => Err(input.clone()),
+            })
+            .collect()
+    }
+
+    /// `--var` flags split into names and values. Flags without `=` are returned as errors.
+    fn named_vars(&self) -> Vec<Result<(String, String), String>> {
+        self.vars
+            .iter()
+            .map(|var| match var.split_once('=') {
+                Some((name, value)) => Ok((name.to_string(), value.to_string())),
+                None => Err(var.clone()),
+            })
+            .collect()
+    }
 }
 
-fn get_hash(input: String) -> String {
-    let digest = digest::digest(&digest::SHA256, input.as_bytes());
-    let hash = HEXLOWER.encode(digest.as_ref());
-    return hash[0..8].to_string();
+/// Prints the error as the last line of the output, or as an `error` event in JSON mode.
+fn report_error(error: &error::Error) {
+    if output::is_json() {
+        output::event(
+            "error",
+            json!({
+                "kind": error.kind(),
+                "message": error.to_string(),
+                "exit_code": error.exit_code(),
+            }),
+        );
+    } else {
+        println!("[{}] error: {}", "SYSTEM".yellow(), error);
+    }
 }
 
-fn main() {
-    let args = Args::parse();
+fn run(args: Args) -> Result<(), error::Error> {
     let mut checker = startup_checker::StartupChecker {
-        args: args,
+        args,
         config: None,
         should_use_ffmpeg_path_field: None,
     };
-    let check_result = checker.check();
-    if check_result {
-        let runner = runner::Runner {
-            args: checker.args,
-            config: checker.config.unwrap(),
-            should_use_ffmpeg_path_field: checker.should_use_ffmpeg_path_field.unwrap(),
-        };
-        runner.run();
+    checker.check()?;
+    let mut runner = runner::Runner::new(
+        checker.args,
+        checker.config.unwrap(),
+        checker.should_use_ffmpeg_path_field.unwrap(),
+    );
+    runner.run()
+}
+
+fn main() {
+    let args = Args::parse();
+    output::set_json(args.output_format == OutputFormat::Json);
+    interrupt::install_handler();
+    if let Err(error) = run(args) {
+        report_error(&error);
+        std::process::exit(error.exit_code());
     }
 }
@@ -0,0 +1,43 @@
+use kffmpeg::execute::Progress;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Prints a line of text for people. Nothing is printed with `--output-format json`.
+macro_rules! text {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_json() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Switches the output to JSON lines. Colors are turned off so that messages in events are plain text.
+pub fn set_json(is_json: bool) {
+    JSON.store(is_json, Ordering::SeqCst);
+    if is_json {
+        colored::control::set_override(false);
+    }
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// Prints an event as a line of JSON on stdout. `fields` must be an object.
+pub fn event(name: &str, fields: Value) {
+    let mut object = Map::new();
+    object.insert("event".to_string(), Value::from(name));
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+    println!("{}", Value::Object(object));
+}
+
+/// Emits a `progress` event for `step`.
+pub fn progress(step: &str, progress: &Progress) {
+    let mut fields = serde_json::to_value(progress).unwrap();
+    fields["step"] = Value::from(step);
+    event("progress", fields);
+}
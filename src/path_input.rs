@@ -0,0 +1,79 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::path::PathBuf;
+
+use crate::get_config_path;
+
+const MAX_HISTORY_SIZE: usize = 100;
+
+struct PathHelper {
+    completer: FilenameCompleter,
+}
+
+impl Completer for PathHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        self.completer.complete(line, pos, ctx)
+    }
+}
+
+impl Hinter for PathHelper {
+    type Hint = String;
+}
+
+impl Highlighter for PathHelper {}
+
+impl Validator for PathHelper {}
+
+impl Helper for PathHelper {}
+
+/// Line editor for paths with filesystem tab completion and a history of recently used paths.
+pub struct PathReader {
+    editor: Editor<PathHelper, FileHistory>,
+    history_path: PathBuf,
+}
+
+impl PathReader {
+    pub fn new() -> rustyline::Result<PathReader> {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .max_history_size(MAX_HISTORY_SIZE)?
+            .history_ignore_dups(true)?
+            .auto_add_history(false)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(PathHelper {
+            completer: FilenameCompleter::new(),
+        }));
+        let history_path = get_config_path().with_file_name("path_history.txt");
+        if history_path.is_file() {
+            editor.load_history(&history_path)?;
+        }
+        Ok(PathReader {
+            editor,
+            history_path,
+        })
+    }
+
+    pub fn read_line(&mut self, prompt: &str) -> rustyline::Result<String> {
+        self.editor.readline(prompt)
+    }
+
+    /// Adds `paths` to the history so that they can be recalled with the up arrow next time.
+    pub fn remember(&mut self, paths: &[PathBuf]) -> rustyline::Result<()> {
+        for path in paths {
+            self.editor.add_history_entry(path.display().to_string())?;
+        }
+        self.editor.save_history(&self.history_path)
+    }
+}
@@ -0,0 +1,126 @@
+//! Paths typed or dropped into a terminal, which may be quoted, escaped or `file://` URIs.
+
+use std::path::{Path, PathBuf};
+
+/// Splits a line into paths the way a POSIX shell splits words, honoring quotes and
+/// backslash-escaped characters. Backslashes are kept as-is on Windows, where they are path separators.
+pub fn split_paths(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') if !cfg!(windows) => match chars.peek() {
+                Some(&next) if next == '"' || next == '\\' => {
+                    current.push(next);
+                    chars.next();
+                }
+                _ => current.push('\\'),
+            },
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, '\\') if !cfg!(windows) => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                idx += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[idx]);
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Normalizes a single path as dropped or typed into a terminal: `file://` URIs are
+/// converted to paths with percent-encoding decoded, and a leading `~` is expanded.
+pub fn normalize_path(token: &str) -> PathBuf {
+    if let Some(rest) = token.strip_prefix("file://") {
+        // Skip the host part, e.g. `file://localhost/home/...`.
+        let path = match rest.find('/') {
+            Some(idx) => &rest[idx..],
+            None => rest,
+        };
+        let mut decoded = percent_decode(path);
+        // `file:///C:/Users/...` on Windows.
+        if cfg!(windows) && decoded.as_bytes().get(2) == Some(&b':') {
+            decoded.remove(0);
+        }
+        return PathBuf::from(decoded);
+    }
+    if token == "~" {
+        if let Some(home) = dirs::home_dir() {
+            return home;
+        }
+    }
+    if let Some(rest) = token
+        .strip_prefix("~/")
+        .or_else(|| token.strip_prefix("~\\"))
+    {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(token)
+}
+
+/// Parses a line of user input into one or more paths.
+/// A line that names an existing path as a whole is taken as a single path, even if it contains spaces.
+pub fn parse_paths(line: &str) -> Vec<PathBuf> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Vec::new();
+    }
+    let whole = normalize_path(line);
+    if Path::exists(&whole) {
+        return vec![whole];
+    }
+    split_paths(line)
+        .iter()
+        .map(|token| normalize_path(token))
+        .collect()
+}
+
+/// Parses a line of user input into a single path, which does not need to exist yet.
+pub fn parse_path(line: &str) -> PathBuf {
+    match split_paths(line.trim()).as_slice() {
+        [token] => normalize_path(token),
+        _ => normalize_path(line.trim()),
+    }
+}
@@ -0,0 +1,392 @@
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::io::{self, Write};
+use std::process;
+
+use crate::error::INTERRUPTED_EXIT_CODE;
+use kffmpeg::TemplateEntry;
+
+/// Result of the full-screen command picker.
+pub enum Pick {
+    Selected(usize),
+    Cancelled,
+}
+
+struct Picker<'a> {
+    commands: &'a [&'a super::Command],
+    ffmpeg_path: &'a str,
+    query: String,
+    matches: Vec<usize>,
+    cursor: usize,
+    scroll: usize,
+}
+
+/// Text the fuzzy filter is matched against for `command`.
+fn search_text(command: &super::Command) -> String {
+    let mut text = vec![command.title.as_str()];
+    text.extend(command.group.as_deref());
+    text.extend(command.tags.iter().map(|tag| tag.as_str()));
+    text.extend(command.description.as_deref());
+    text.join(" ")
+}
+
+/// `template` with `{{ffmpeg_path}}` and `{{options}}` expanded, as shown in the preview pane.
+fn expand_template(
+    command: &super::Command,
+    template: &[TemplateEntry],
+    ffmpeg_path: &str,
+) -> String {
+    let expand = |s: &String| {
+        if s == "{{options}}" {
+            command
+                .options
+                .iter()
+                .map(|option| match &option.when {
+                    Some(when) => format!("[when {}: {} {}]", when, option.flag, option.value),
+                    None => format!("{} {}", option.flag, option.value),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            s.replace("{{ffmpeg_path}}", ffmpeg_path)
+        }
+    };
+    template
+        .iter()
+        .map(|entry| {
+            let args: Vec<String> = entry.args().iter().map(expand).collect();
+            match entry.condition() {
+                Some(when) => format!("[when {}: {}]", when, args.join(" ")),
+                None => args.join(" "),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Splits `text` into lines of at most `width` characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if width == 0 || chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+impl<'a> Picker<'a> {
+    fn update_matches(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, usize)> = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, command)| {
+                if self.query.is_empty() {
+                    Some((0, idx))
+                } else {
+                    matcher
+                        .fuzzy_match(search_text(command).as_str(), self.query.as_str())
+                        .map(|score| (score, idx))
+                }
+            })
+            .collect();
+        // Stable sort keeps the config order among equal scores.
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.matches = scored.into_iter().map(|(_, idx)| idx).collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        self.cursor = (self.cursor as isize + delta).clamp(0, last) as usize;
+    }
+
+    fn preview_lines(&self, width: usize) -> Vec<String> {
+        let command = match self.matches.get(self.cursor) {
+            Some(idx) => &self.commands[*idx],
+            None => return vec!["No command matches the filter.".to_string()],
+        };
+        let mut lines = wrap(command.title.as_str(), width);
+        lines.push(format!("hash: {}", super::get_hash(command.title.clone())));
+        if let Some(group) = &command.group {
+            lines.push(format!("group: {}", group));
+        }
+        if !command.tags.is_empty() {
+            lines.extend(wrap(
+                format!("tags: {}", command.tags.join(", ")).as_str(),
+                width,
+            ));
+        }
+        if let Some(description) = &command.description {
+            lines.push(String::new());
+            for line in description.lines() {
+                lines.extend(wrap(line, width));
+            }
+        }
+        if !command.extensions.is_empty() || !command.requires.is_empty() {
+            lines.push(String::new());
+            if !command.extensions.is_empty() {
+                lines.extend(wrap(
+                    format!("Extensions: {}", command.extensions.join(", ")).as_str(),
+                    width,
+                ));
+            }
+            if !command.requires.is_empty() {
+                lines.extend(wrap(
+                    format!("Requires: {}", command.requires.join(", ")).as_str(),
+                    width,
+                ));
+            }
+        }
+        if !command.inputs.is_empty() {
+            lines.push(String::new());
+            lines.push("Inputs".to_string());
+            for input in command.inputs.iter() {
+                let line = if input.extensions.is_empty() {
+                    format!("  {}", input.name)
+                } else {
+                    format!("  {} ({})", input.name, input.extensions.join(", "))
+                };
+                lines.extend(wrap(line.as_str(), width));
+            }
+        }
+        lines.push(String::new());
+        lines.push("Options".to_string());
+        if command.options.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        for option in command.options.iter() {
+            lines.extend(wrap(
+                format!("  {} {}", option.flag, option.value).as_str(),
+                width,
+            ));
+        }
+        if !command.encoders.is_empty() {
+            lines.push(String::new());
+            lines.push("Encoders (first available)".to_string());
+            for encoder in command.encoders.iter() {
+                let mut line = format!("  {}", encoder.name);
+                for option in encoder.options.iter() {
+                    line.push_str(format!(" {} {}", option.flag, option.value).as_str());
+                }
+                lines.extend(wrap(line.as_str(), width));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "Output: {{input}}{}{}",
+            command.output_filename_suffix, command.output_extension
+        ));
+        lines.push(String::new());
+        if command.steps.is_empty() {
+            lines.push("Command".to_string());
+            lines.extend(wrap(
+                expand_template(command, &command.command, self.ffmpeg_path).as_str(),
+                width,
+            ));
+        } else {
+            lines.push("Steps".to_string());
+            for step in command.steps.iter() {
+                lines.extend(wrap(
+                    format!(
+                        "{}: {}",
+                        step.name,
+                        expand_template(command, &step.command, self.ffmpeg_path)
+                    )
+                    .as_str(),
+                    width,
+                ));
+            }
+        }
+        lines
+    }
+
+    fn draw(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        let (columns, rows) = (columns as usize, rows as usize);
+        let list_width = (columns * 2 / 5).max(20).min(columns);
+        let preview_x = list_width + 1;
+        let preview_width = columns.saturating_sub(preview_x + 1);
+        let list_height = rows.saturating_sub(3);
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if list_height > 0 && self.cursor >= self.scroll + list_height {
+            self.scroll = self.cursor + 1 - list_height;
+        }
+
+        queue!(
+            stdout,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            SetForegroundColor(Color::Cyan),
+            Print("filter > "),
+            SetForegroundColor(Color::Reset),
+            Print(truncate(self.query.as_str(), columns.saturating_sub(9))),
+            cursor::MoveTo(0, 1),
+            SetForegroundColor(Color::DarkGrey),
+            Print(truncate(
+                format!(
+                    "{}/{}  ↑↓: move  Enter: select  Esc: quit",
+                    self.matches.len(),
+                    self.commands.len()
+                )
+                .as_str(),
+                columns,
+            )),
+            SetForegroundColor(Color::Reset),
+        )?;
+
+        for (row, idx) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_height)
+        {
+            let command = self.commands[*idx];
+            let title = truncate(
+                match &command.group {
+                    Some(group) => format!("{} / {}", group, command.title),
+                    None => command.title.clone(),
+                }
+                .as_str(),
+                list_width.saturating_sub(2),
+            );
+            queue!(stdout, cursor::MoveTo(0, (row - self.scroll + 2) as u16))?;
+            if row == self.cursor {
+                queue!(
+                    stdout,
+                    SetForegroundColor(Color::Green),
+                    SetAttribute(Attribute::Bold),
+                    Print(format!("> {}", title)),
+                    SetAttribute(Attribute::Reset),
+                    SetForegroundColor(Color::Reset),
+                )?;
+            } else {
+                queue!(stdout, Print(format!("  {}", title)))?;
+            }
+        }
+
+        if preview_width > 0 {
+            for row in 2..rows {
+                queue!(
+                    stdout,
+                    cursor::MoveTo(list_width as u16, row as u16),
+                    SetForegroundColor(Color::DarkGrey),
+                    Print("│"),
+                    SetForegroundColor(Color::Reset),
+                )?;
+            }
+            for (row, line) in self
+                .preview_lines(preview_width)
+                .iter()
+                .take(rows.saturating_sub(2))
+                .enumerate()
+            {
+                queue!(
+                    stdout,
+                    cursor::MoveTo(preview_x as u16, (row + 2) as u16),
+                    Print(truncate(line.as_str(), preview_width)),
+                )?;
+            }
+        }
+
+        queue!(
+            stdout,
+            cursor::MoveTo((9 + self.query.chars().count()).min(columns) as u16, 0)
+        )?;
+        stdout.flush()
+    }
+
+    /// Returns `Some` when the key finishes the picker.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Pick> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => {
+                leave_screen();
+                println!("[{}] Interrupted.", "SYSTEM".yellow());
+                process::exit(INTERRUPTED_EXIT_CODE);
+            }
+            KeyCode::Char('p') if ctrl => self.move_cursor(-1),
+            KeyCode::Char('n') if ctrl => self.move_cursor(1),
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-10),
+            KeyCode::PageDown => self.move_cursor(10),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.matches.len().saturating_sub(1),
+            KeyCode::Enter => {
+                if let Some(idx) = self.matches.get(self.cursor) {
+                    return Some(Pick::Selected(*idx));
+                }
+            }
+            KeyCode::Esc => return Some(Pick::Cancelled),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+fn leave_screen() {
+    let mut stdout = io::stdout();
+    let _ = terminal::disable_raw_mode();
+    let _ = queue!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = stdout.flush();
+}
+
+/// Shows a full-screen picker with type-to-filter fuzzy search and a preview pane.
+/// The caller must make sure that stdin and stdout are terminals.
+pub fn pick_command(commands: &[&super::Command], ffmpeg_path: &str) -> io::Result<Pick> {
+    let mut picker = Picker {
+        commands,
+        ffmpeg_path,
+        query: String::new(),
+        matches: Vec::new(),
+        cursor: 0,
+        scroll: 0,
+    };
+    picker.update_matches();
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    queue!(stdout, terminal::EnterAlternateScreen)?;
+
+    let result = (|| loop {
+        picker.draw(&mut stdout)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if let Some(pick) = picker.handle_key(key) {
+                return Ok(pick);
+            }
+        }
+    })();
+
+    leave_screen();
+    result
+}
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command as ProcessCommand;
+
+/// Output of `ffprobe -show_format -show_streams`. Only the fields kffmpeg uses are read.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProbeResult {
+    pub format: Format,
+    #[serde(default)]
+    pub streams: Vec<Stream>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Format {
+    pub duration: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Stream {
+    pub codec_type: String,
+    pub codec_name: Option<String>,
+    pub bit_rate: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub pix_fmt: Option<String>,
+    pub r_frame_rate: Option<String>,
+    pub sample_rate: Option<String>,
+    pub channels: Option<u64>,
+}
+
+impl ProbeResult {
+    /// Duration in seconds.
+    pub fn duration(&self) -> Option<f64> {
+        self.format.duration.as_ref()?.parse().ok()
+    }
+
+    pub fn streams_of_type<'a>(&'a self, codec_type: &'a str) -> impl Iterator<Item = &'a Stream> {
+        self.streams
+            .iter()
+            .filter(move |stream| stream.codec_type == codec_type)
+    }
+
+    /// Total bitrate of the audio streams in bits per second.
+    /// Returns `None` when any audio stream does not report its bitrate.
+    pub fn audio_bitrate(&self) -> Option<u64> {
+        self.streams_of_type("audio")
+            .map(|stream| stream.bit_rate.as_ref()?.parse::<u64>().ok())
+            .sum()
+    }
+}
+
+/// Path of ffprobe that sits next to `ffmpeg_path`, e.g. `/usr/bin/ffprobe` for `/usr/bin/ffmpeg`.
+pub fn ffprobe_path_for(ffmpeg_path: &str) -> String {
+    let path = Path::new(ffmpeg_path);
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains("ffmpeg") => path
+            .with_file_name(name.replacen("ffmpeg", "ffprobe", 1))
+            .display()
+            .to_string(),
+        _ => "ffprobe".to_string(),
+    }
+}
+
+pub fn probe(ffprobe_path: &str, path: &Path) -> Result<ProbeResult, String> {
+    let output = ProcessCommand::new(ffprobe_path)
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|error| format!("Unable to run {}: {}", ffprobe_path, error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe could not read {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|error| format!("Unable to parse the output of ffprobe: {}", error))
+}
@@ -0,0 +1,224 @@
+//! Objective quality of an encoded video against its source, measured with the `psnr`, `ssim` and
+//! `libvmaf` filters of ffmpeg.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command as ProcessCommand;
+
+use crate::probe;
+
+/// Scores of the distorted video. `None` when the metric was not measured, e.g. VMAF with an
+/// ffmpeg built without libvmaf. PSNR is infinite for identical videos.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Scores {
+    /// Average PSNR of all planes in dB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub psnr: Option<f64>,
+    /// SSIM of all planes, from 0 to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssim: Option<f64>,
+    /// VMAF score, from 0 to 100.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vmaf: Option<f64>,
+}
+
+impl Scores {
+    /// e.g. `PSNR 38.21 dB, SSIM 0.9812, VMAF 93.40`, leaving out the metrics that were not measured.
+    pub fn summary(&self) -> String {
+        let mut scores = Vec::new();
+        if let Some(psnr) = self.psnr {
+            scores.push(format!("PSNR {:.2} dB", psnr));
+        }
+        if let Some(ssim) = self.ssim {
+            scores.push(format!("SSIM {:.4}", ssim));
+        }
+        if let Some(vmaf) = self.vmaf {
+            scores.push(format!("VMAF {:.2}", vmaf));
+        }
+        scores.join(", ")
+    }
+}
+
+/// Size, duration and average bitrate of a file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileStats {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    /// Average bitrate of the whole file in bits per second.
+    pub bitrate: Option<u64>,
+}
+
+/// Result of comparing `distorted` with `reference`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub reference: FileStats,
+    pub distorted: FileStats,
+    pub scores: Scores,
+}
+
+/// Names of the filters in the output of `ffmpeg -filters`, whose lines are
+/// `<flags> <name> <inputs>-><outputs> <description>`, e.g. ` ... psnr  VV->V  Calculate ...`.
+pub fn parse_filters(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, name, io, ..] if io.contains("->") => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Filters that `ffmpeg_path` was built with.
+pub fn list_filters(ffmpeg_path: &str) -> Result<HashSet<String>, String> {
+    let output = ProcessCommand::new(ffmpeg_path)
+        .args(["-hide_banner", "-filters"])
+        .output()
+        .map_err(|error| format!("Unable to run {}: {}", ffmpeg_path, error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} could not list the filters: {}",
+            ffmpeg_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_filters(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Arguments of ffmpeg, without the executable, that compute `metrics` such as `psnr` of
+/// `distorted` against `reference`. The distorted video is scaled to `width`x`height`, the size
+/// of the reference, and both start at time zero so that the frames line up.
+pub fn compare_args(
+    reference: &Path,
+    distorted: &Path,
+    width: u64,
+    height: u64,
+    metrics: &[&str],
+) -> Vec<String> {
+    let labels = |prefix: &str| -> String {
+        (0..metrics.len())
+            .map(|idx| format!("[{}{}]", prefix, idx))
+            .collect()
+    };
+    let mut graph = vec![
+        format!(
+            "[0:v]scale={}:{}:flags=bicubic,settb=AVTB,setpts=PTS-STARTPTS,split={}{}",
+            width,
+            height,
+            metrics.len(),
+            labels("d")
+        ),
+        format!(
+            "[1:v]settb=AVTB,setpts=PTS-STARTPTS,split={}{}",
+            metrics.len(),
+            labels("r")
+        ),
+    ];
+    graph.extend(
+        metrics
+            .iter()
+            .enumerate()
+            .map(|(idx, metric)| format!("[d{}][r{}]{}", idx, idx, metric)),
+    );
+    let mut args: Vec<String> = ["-hide_banner", "-nostats", "-i"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.push(distorted.display().to_string());
+    args.push("-i".to_string());
+    args.push(reference.display().to_string());
+    args.extend(
+        ["-lavfi", graph.join(";").as_str(), "-an", "-f", "null", "-"]
+            .iter()
+            .map(|s| s.to_string()),
+    );
+    args
+}
+
+/// Scores in the stderr of ffmpeg run with [`compare_args`], from lines such as
+/// `[Parsed_psnr_4 @ 0x5581] PSNR y:34.95 u:41.21 v:41.51 average:36.38 min:33.71 max:39.82`,
+/// `[Parsed_ssim_5 @ 0x5581] SSIM Y:0.957 (13.66) U:0.978 (16.54) V:0.979 (16.80) All:0.965 (14.55)`
+/// and `[Parsed_libvmaf_6 @ 0x5581] VMAF score: 93.452`.
+pub fn parse_scores(stderr: &str) -> Scores {
+    let find = |pattern: &str| -> Option<f64> {
+        Regex::new(pattern)
+            .unwrap()
+            .captures(stderr)?
+            .get(1)?
+            .as_str()
+            .parse()
+            .ok()
+    };
+    Scores {
+        psnr: find(r"PSNR .*average:(inf|[\d.]+)"),
+        ssim: find(r"SSIM .*All:([\d.]+)"),
+        vmaf: find(r"VMAF score: ([\d.]+)"),
+    }
+}
+
+fn file_stats(path: &Path, probe_result: Option<&probe::ProbeResult>) -> Result<FileStats, String> {
+    let size = fs::metadata(path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?
+        .len();
+    let duration = probe_result
+        .and_then(|probe_result| probe_result.duration())
+        .filter(|duration| *duration > 0.0);
+    Ok(FileStats {
+        path: path.to_path_buf(),
+        size,
+        duration,
+        bitrate: duration.map(|duration| (size as f64 * 8.0 / duration) as u64),
+    })
+}
+
+/// Measures PSNR and SSIM of `distorted` against `reference`, and VMAF when ffmpeg has libvmaf.
+pub fn measure(
+    ffmpeg_path: &str,
+    ffprobe_path: &str,
+    reference: &Path,
+    distorted: &Path,
+) -> Result<QualityReport, String> {
+    let reference_probe = probe::probe(ffprobe_path, reference)?;
+    let video = reference_probe
+        .streams_of_type("video")
+        .next()
+        .ok_or(format!("{} has no video stream.", reference.display()))?;
+    let (Some(width), Some(height)) = (video.width, video.height) else {
+        return Err(format!(
+            "Unable to get the resolution of {}.",
+            reference.display()
+        ));
+    };
+    let mut metrics = vec!["psnr", "ssim"];
+    if list_filters(ffmpeg_path)?.contains("libvmaf") {
+        metrics.push("libvmaf");
+    }
+    let output = ProcessCommand::new(ffmpeg_path)
+        .args(compare_args(reference, distorted, width, height, &metrics))
+        .output()
+        .map_err(|error| format!("Unable to run {}: {}", ffmpeg_path, error))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg could not compare the videos: {}",
+            stderr.lines().last().unwrap_or_default()
+        ));
+    }
+    let scores = parse_scores(&stderr);
+    if scores.psnr.is_none() && scores.ssim.is_none() {
+        return Err("ffmpeg did not report the scores.".to_string());
+    }
+    let distorted_probe = probe::probe(ffprobe_path, distorted).ok();
+    Ok(QualityReport {
+        reference: file_stats(reference, Some(&reference_probe))?,
+        distorted: file_stats(distorted, distorted_probe.as_ref())?,
+        scores,
+    })
+}
@@ -0,0 +1,289 @@
+//! Summary of the jobs of a run, written as CSV, JSON or a Markdown table.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::size::format_size;
+
+/// A finished job as listed in the report.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct JobReport {
+    /// The files the job was run on. A concat job has all the joined files.
+    pub input_paths: Vec<PathBuf>,
+    pub output_path: PathBuf,
+    /// Hash of the command.
+    pub hash: String,
+    pub title: String,
+    /// Duration of the input in seconds, added up for a concat job.
+    pub duration: Option<f64>,
+    /// Seconds the job took to run.
+    pub wall_time: f64,
+    pub input_size: Option<u64>,
+    pub output_size: Option<u64>,
+    /// [`compression_ratio`] of a job that succeeded.
+    pub compression_ratio: Option<f64>,
+    /// Scores measured with `--measure-quality`, as in [`crate::quality::Scores`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psnr: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssim: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vmaf: Option<f64>,
+    pub succeeded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JobReport {
+    fn status(&self) -> &'static str {
+        if self.succeeded {
+            "succeeded"
+        } else {
+            "failed"
+        }
+    }
+}
+
+/// A column of a quality metric, with the name used in CSV and in Markdown, and the number of
+/// decimals.
+struct QualityColumn {
+    csv_name: &'static str,
+    markdown_name: &'static str,
+    decimals: usize,
+    score: fn(&JobReport) -> Option<f64>,
+}
+
+const QUALITY_COLUMNS: [QualityColumn; 3] = [
+    QualityColumn {
+        csv_name: "psnr",
+        markdown_name: "PSNR",
+        decimals: 2,
+        score: |job| job.psnr,
+    },
+    QualityColumn {
+        csv_name: "ssim",
+        markdown_name: "SSIM",
+        decimals: 4,
+        score: |job| job.ssim,
+    },
+    QualityColumn {
+        csv_name: "vmaf",
+        markdown_name: "VMAF",
+        decimals: 2,
+        score: |job| job.vmaf,
+    },
+];
+
+/// Columns of the metrics that at least one of `jobs` has, so that a report without
+/// `--measure-quality` has no empty columns.
+fn quality_columns(jobs: &[JobReport]) -> Vec<&'static QualityColumn> {
+    QUALITY_COLUMNS
+        .iter()
+        .filter(|column| jobs.iter().any(|job| (column.score)(job).is_some()))
+        .collect()
+}
+
+fn format_score(job: &JobReport, column: &QualityColumn) -> String {
+    format_option((column.score)(job), |score| {
+        format!("{:.*}", column.decimals, score)
+    })
+}
+
+/// Input size divided by output size, e.g. `2.0` for an output of half the size.
+pub fn compression_ratio(input_size: Option<u64>, output_size: Option<u64>) -> Option<f64> {
+    match (input_size, output_size) {
+        (Some(input_size), Some(output_size)) if output_size > 0 => {
+            Some(input_size as f64 / output_size as f64)
+        }
+        _ => None,
+    }
+}
+
+/// Totals over the jobs. The sizes are of the jobs that succeeded.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Totals {
+    pub jobs: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub wall_time: f64,
+    pub input_size: u64,
+    pub output_size: u64,
+    /// Input size minus output size, which is negative when the outputs are larger.
+    pub saved_size: i64,
+}
+
+impl Totals {
+    pub fn of(jobs: &[JobReport]) -> Totals {
+        let succeeded: Vec<&JobReport> = jobs.iter().filter(|job| job.succeeded).collect();
+        let input_size: u64 = succeeded.iter().filter_map(|job| job.input_size).sum();
+        let output_size: u64 = succeeded.iter().filter_map(|job| job.output_size).sum();
+        Totals {
+            jobs: jobs.len(),
+            succeeded: succeeded.len(),
+            failed: jobs.len() - succeeded.len(),
+            wall_time: jobs.iter().map(|job| job.wall_time).sum(),
+            input_size,
+            output_size,
+            saved_size: input_size as i64 - output_size as i64,
+        }
+    }
+
+    /// e.g. `3 jobs, 2 succeeded, 1 failed in 95.2s. 1.20 GB -> 480.00 MB, 720.00 MB (60%) saved.`
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} jobs, {} succeeded, {} failed in {:.1}s.",
+            self.jobs, self.succeeded, self.failed, self.wall_time
+        );
+        if self.input_size > 0 {
+            summary.push_str(&format!(
+                " {} -> {}, {} ({:.0}%) {}.",
+                format_size(self.input_size),
+                format_size(self.output_size),
+                format_size(self.saved_size.unsigned_abs()),
+                (self.saved_size as f64 / self.input_size as f64 * 100.0).abs(),
+                if self.saved_size >= 0 {
+                    "saved"
+                } else {
+                    "added"
+                },
+            ));
+        }
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Format for the extension of `path`: `.csv`, `.json`, or `.md` for Markdown.
+    pub fn from_path(path: &Path) -> Option<ReportFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// The report of `jobs` in `format`.
+pub fn render(jobs: &[JobReport], format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Csv => render_csv(jobs),
+        ReportFormat::Json => {
+            let report = serde_json::json!({ "jobs": jobs, "total": Totals::of(jobs) });
+            serde_json::to_string_pretty(&report).unwrap() + "\n"
+        }
+        ReportFormat::Markdown => render_markdown(jobs),
+    }
+}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn format_option<T>(value: Option<T>, format: impl Fn(T) -> String) -> String {
+    value.map_or(String::new(), format)
+}
+
+/// Quotes `field` when it has a comma, a quote or a line break, as RFC 4180 does.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One row per job with the sizes in bytes and the times in seconds.
+fn render_csv(jobs: &[JobReport]) -> String {
+    let columns = quality_columns(jobs);
+    let mut csv =
+        "input,output,hash,title,duration,wall_time,input_size,output_size,compression_ratio"
+            .to_string();
+    for column in columns.iter() {
+        csv.push(',');
+        csv.push_str(column.csv_name);
+    }
+    csv.push_str(",status,error\n");
+    for job in jobs {
+        let mut fields = vec![
+            join_paths(&job.input_paths),
+            job.output_path.display().to_string(),
+            job.hash.clone(),
+            job.title.clone(),
+            format_option(job.duration, |duration| format!("{:.3}", duration)),
+            format!("{:.3}", job.wall_time),
+            format_option(job.input_size, |size| size.to_string()),
+            format_option(job.output_size, |size| size.to_string()),
+            format_option(job.compression_ratio, |ratio| format!("{:.3}", ratio)),
+        ];
+        fields.extend(columns.iter().map(|column| format_score(job, column)));
+        fields.push(job.status().to_string());
+        fields.push(job.error.clone().unwrap_or_default());
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
+
+fn file_names(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| {
+            path.file_name().map_or(path.display().to_string(), |name| {
+                name.to_string_lossy().to_string()
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// A table with the file names and readable sizes, followed by the totals.
+fn render_markdown(jobs: &[JobReport]) -> String {
+    let columns = quality_columns(jobs);
+    let mut header =
+        "| Input | Output | Command | Duration | Wall time | Input size | Output size | Ratio |"
+            .to_string();
+    let mut alignment = "| --- | --- | --- | ---: | ---: | ---: | ---: | ---: |".to_string();
+    for column in columns.iter() {
+        header.push_str(&format!(" {} |", column.markdown_name));
+        alignment.push_str(" ---: |");
+    }
+    let mut markdown = format!("{} Status |\n{} --- |\n", header, alignment);
+    for job in jobs {
+        let mut cells = vec![
+            file_names(&job.input_paths),
+            file_names(std::slice::from_ref(&job.output_path)),
+            format!("{} ({})", job.title, job.hash),
+            format_option(job.duration, |duration| format!("{:.1}s", duration)),
+            format!("{:.1}s", job.wall_time),
+            format_option(job.input_size, format_size),
+            format_option(job.output_size, format_size),
+            format_option(job.compression_ratio, |ratio| format!("{:.2}x", ratio)),
+        ];
+        cells.extend(columns.iter().map(|column| format_score(job, column)));
+        cells.push(match &job.error {
+            Some(error) => format!("failed: {}", error),
+            None => job.status().to_string(),
+        });
+        let cells: Vec<String> = cells.iter().map(|cell| markdown_cell(cell)).collect();
+        markdown.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    markdown.push_str(&format!("\n{}\n", Totals::of(jobs).summary()));
+    markdown
+}
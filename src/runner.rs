@@ -1,193 +1,968 @@
+use crate::error::Error;
+use crate::job_log::JobLog;
+use crate::path_input::PathReader;
+use crate::picker::{self, Pick};
+use crate::{concat, config_editor, history, interrupt, output, watcher};
 use colored::Colorize;
-use regex::Regex;
-use std::io::{self, Write};
+use kffmpeg::job::{self, JobRequest, JobResult};
+use kffmpeg::paths;
+use kffmpeg::size::{format_size, parse_size};
+use kffmpeg::template::ExpandedStep;
+use kffmpeg::{execute, quality, report, verify, Encoder};
+use rustyline::error::ReadlineError;
+use serde_json::json;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 pub struct Runner {
     pub args: super::Args,
     pub config: super::Config,
     pub should_use_ffmpeg_path_field: bool,
+    /// Plans and runs every job. The probes it caches are dropped when a job ends, since a
+    /// watched directory can get a new file of the same name.
+    pub jobs: job::Context,
+    /// Every job run so far, for the batch summary and `--report`.
+    pub reports: RefCell<Vec<report::JobReport>>,
 }
-use std::process::Command as ProcessCommand;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
 use std::process::Stdio;
 
+const DEFAULT_LOG_TAIL_LINES: usize = 20;
+
 impl Runner {
-    pub fn run(&self) {
-        if self.args.hash != None && self.args.input_path != None {
-            let hash = self.args.hash.clone().unwrap();
-            let input_path = self.args.input_path.clone().unwrap();
-            let command = self
-                .config
-                .commands
-                .iter()
-                .find(|command| super::get_hash(command.title.clone()) == hash)
-                .unwrap();
-            let options: Vec<_> = command
-                .options
-                .iter()
-                .flat_map(|option| vec![option.flag.clone(), option.value.clone()])
-                .collect();
-            let output_path = Path::new(input_path.as_str()).parent().unwrap().join(
-                Path::new(input_path.as_str())
-                    .file_stem()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string()
-                    + command.output_filename_suffix.as_str()
-                    + command.output_extension.as_str(),
+    /// A runner whose jobs are run by ffmpeg, or only shown with `--dry-run`.
+    pub fn new(
+        args: super::Args,
+        config: super::Config,
+        should_use_ffmpeg_path_field: bool,
+    ) -> Runner {
+        let executor: Box<dyn execute::Executor> = if args.dry_run {
+            Box::new(execute::DryRunExecutor)
+        } else {
+            Box::new(execute::ProcessExecutor)
+        };
+        let log_dir = match &config.log_dir {
+            Some(log_dir) => paths::parse_path(log_dir),
+            None => super::get_config_path().with_file_name("logs"),
+        };
+        let jobs = job::Context::new(
+            if should_use_ffmpeg_path_field {
+                config.ffmpeg_path.as_str()
+            } else {
+                "ffmpeg"
+            },
+            config.ffprobe_path(should_use_ffmpeg_path_field).as_str(),
+            executor,
+            &log_dir,
+        )
+        .with_dry_run(args.dry_run)
+        .with_verify(args.verify)
+        .with_measure_quality(args.measure_quality);
+        Runner {
+            args,
+            config,
+            should_use_ffmpeg_path_field,
+            jobs,
+            reports: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        let result = self.run_selected();
+        self.print_summary();
+        result
+    }
+
+    /// Runs what the arguments select: a subcommand, the command of `--hash`, or the interactive
+    /// mode.
+    fn run_selected(&mut self) -> Result<(), Error> {
+        if let Some(super::Subcommand::Watch {
+            dir,
+            command,
+            output_dir,
+        }) = &self.args.subcommand
+        {
+            self.watch(dir, command, output_dir.as_deref())
+        } else if let Some(super::Subcommand::History { query, limit }) = &self.args.subcommand {
+            self.show_history(query.as_deref(), *limit)
+        } else if let Some(super::Subcommand::Rerun { number, input }) = &self.args.subcommand {
+            self.rerun(*number, input.as_deref())
+        } else if let Some(super::Subcommand::Compare {
+            reference,
+            distorted,
+        }) = &self.args.subcommand
+        {
+            self.compare(reference, distorted)
+        } else if let Some(hash) = &self.args.hash {
+            let command = self.config.find_command(hash).unwrap();
+            self.report_command(command);
+            let request = self.request_from_args(command)?;
+            self.execute_job(request, false).result
+        } else {
+            self.print_message(
+                "Type 'q' to quit at any prompt other than a value or a title.",
+                true,
             );
-            self.execute_command_no_interaction(
-                command,
-                Path::new(input_path.as_str()).to_path_buf(),
-                options,
-                output_path,
+            // A copy, since saving the options below changes the config.
+            let command = &self.get_command()?.clone();
+            let (concat_source, input_paths) = if command.concat {
+                let (source, input_paths) = self.get_concat_input_paths(command)?;
+                (Some(source), input_paths)
+            } else {
+                (None, self.get_input_paths(&command.first_input())?)
+            };
+            let inputs = self.get_named_inputs(command)?;
+            let encoder = self
+                .jobs
+                .available_encoder(command)?
+                .map(|encoder| encoder.name.clone());
+            let edited = self.get_options(command, encoder.as_deref())?;
+            if edited != *command {
+                self.save_options(command, &edited)?;
+            }
+            let command = &edited;
+            let options = command.options.clone();
+            let target_size = self
+                .target_size_arg(command)
+                .map(|size| self.get_target_size(size))
+                .transpose()?;
+            if concat_source.is_none() && input_paths.len() > 1 {
+                return self.run_batch(command, input_paths, &inputs, options, target_size);
+            }
+            let source = concat_source.unwrap_or_else(|| input_paths[0].clone());
+            let output_path = self.get_output_path(command.default_output_path(&source)?)?;
+            self.execute_job(
+                JobRequest {
+                    command,
+                    input_paths,
+                    inputs,
+                    options,
+                    vars: self.vars(command),
+                    output_path,
+                    target_size,
+                },
+                true,
+            )
+            .result
+        }
+    }
+
+    /// The job given by `--hash`, `--input_path` and `--input`.
+    fn request_from_args<'a>(&self, command: &'a super::Command) -> Result<JobRequest<'a>, Error> {
+        let (input_path, inputs) = self.input_args(command);
+        let input_paths = if command.concat {
+            concat::list_directory(&input_path, &command.first_input())?
+        } else {
+            vec![input_path.clone()]
+        };
+        Ok(JobRequest {
+            command,
+            input_paths,
+            inputs,
+            options: command.options.clone(),
+            vars: self.vars(command),
+            output_path: command.default_output_path(&input_path)?,
+            target_size: self.default_target_size(command),
+        })
+    }
+
+    /// Target size given by `--target-size`, or by the command. Commands without `target_size`
+    /// ignore `--target-size`.
+    fn target_size_arg<'a>(&'a self, command: &'a super::Command) -> Option<&'a str> {
+        command
+            .target_size
+            .as_ref()
+            .map(|size| self.args.target_size.as_ref().unwrap_or(size).as_str())
+    }
+
+    /// Values of `var.<name>` for the command: its `variables`, overridden by `--var`.
+    fn vars(&self, command: &super::Command) -> BTreeMap<String, String> {
+        let mut vars = command.variables.clone();
+        vars.extend(self.var_args());
+        vars
+    }
+
+    /// Values given by `--var`.
+    fn var_args(&self) -> impl Iterator<Item = (String, String)> {
+        self.args
+            .named_vars()
+            .into_iter()
+            .filter_map(|var| var.ok())
+    }
+
+    /// Target size of the command in bytes without asking. It was validated at startup.
+    fn default_target_size(&self, command: &super::Command) -> Option<u64> {
+        self.target_size_arg(command)
+            .map(|size| parse_size(size).unwrap())
+    }
+
+    /// Runs the command on every file put in `dir` until interrupted, and moves each file into
+    /// `done/` or `failed/` afterwards.
+    fn watch(&self, dir: &Path, hash: &str, output_dir: Option<&Path>) -> Result<(), Error> {
+        let command = self.config.find_command(hash).unwrap();
+        self.report_command(command);
+        let input = command.first_input();
+        let inputs: HashMap<String, PathBuf> = self
+            .args
+            .named_inputs()
+            .into_iter()
+            .filter_map(|input| input.ok())
+            .collect();
+        let output_dir = output_dir
+            .map(|output_dir| output_dir.to_path_buf())
+            .unwrap_or(dir.join("output"));
+        let done_dir = dir.join("done");
+        let failed_dir = dir.join("failed");
+        for dir in [&output_dir, &done_dir, &failed_dir] {
+            fs::create_dir_all(dir).map_err(|error| {
+                Error::Other(format!("Unable to create {}: {}", dir.display(), error))
+            })?;
+        }
+
+        let mut stable_files = watcher::StableFiles::new(dir).map_err(|error| {
+            Error::Other(format!("Unable to watch {}: {}", dir.display(), error))
+        })?;
+        self.print_message(
+            format!(
+                "Watching {} with {}. Press Ctrl+C to stop.",
+                dir.display().to_string().bold(),
+                command.title.bold()
+            )
+            .as_str(),
+            true,
+        );
+        text!();
+        loop {
+            let Some(paths) = stable_files
+                .next(interrupt::was_interrupted)
+                .map_err(Error::Other)?
+            else {
+                return Err(Error::Interrupted);
+            };
+            for path in paths {
+                self.print_message(
+                    format!("Found {}", path.display().to_string().bold()).as_str(),
+                    true,
+                );
+                let succeeded = match self.input_path_problem(&path, &input) {
+                    Some(problem) => {
+                        self.print_message(problem.as_str(), true);
+                        false
+                    }
+                    None => {
+                        let result = command.default_output_path(&path).and_then(|output_path| {
+                            let request = JobRequest {
+                                command,
+                                input_paths: vec![path.clone()],
+                                inputs: inputs.clone(),
+                                options: command.options.clone(),
+                                vars: self.vars(command),
+                                output_path: output_dir.join(output_path.file_name().unwrap()),
+                                target_size: self.default_target_size(command),
+                            };
+                            self.execute_job(request, false).result
+                        });
+                        if let Err(error) = &result {
+                            self.print_message(format!("error: {}", error).as_str(), true);
+                        }
+                        result.is_ok()
+                    }
+                };
+                if interrupt::was_interrupted() {
+                    self.print_message(
+                        format!("{} was left in place.", path.display()).as_str(),
+                        true,
+                    );
+                    return Err(Error::Interrupted);
+                }
+                let destination = if succeeded { &done_dir } else { &failed_dir };
+                match watcher::move_into(&path, destination) {
+                    Ok(moved) => {
+                        self.print_message(format!("Moved to {}", moved.display()).as_str(), true)
+                    }
+                    Err(error) => self.print_message(format!("error: {}", error).as_str(), true),
+                }
+                text!();
+            }
+        }
+    }
+
+    /// Lists the last `limit` jobs in the history that match `query`.
+    fn show_history(&self, query: Option<&str>, limit: usize) -> Result<(), Error> {
+        let entries = history::load().map_err(Error::Other)?;
+        let numbered: Vec<(usize, &history::HistoryEntry)> = entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (idx + 1, entry))
+            .filter(|(_, entry)| query.is_none_or(|query| entry.matches(query)))
+            .collect();
+        if numbered.is_empty() {
+            self.print_message("No jobs found in the history.", true);
+            return Ok(());
+        }
+        self.print_message(
+            format!("Jobs in {}", history::history_path().display()).as_str(),
+            true,
+        );
+        for (number, entry) in numbered.iter().skip(numbered.len().saturating_sub(limit)) {
+            if output::is_json() {
+                let mut fields = serde_json::to_value(entry).unwrap();
+                fields["number"] = json!(number);
+                output::event("history", fields);
+                continue;
+            }
+            text!(
+                "{:>6}  {}  {}  {} ({})",
+                number.to_string().green(),
+                entry.timestamp,
+                if entry.succeeded {
+                    "OK".green()
+                } else {
+                    "NG".red()
+                },
+                entry.title.bold(),
+                entry.hash.bright_cyan()
+            );
+            let input = match entry.input_paths.as_slice() {
+                [input_path] => input_path.display().to_string(),
+                [first, rest @ ..] => format!("{} and {} more", first.display(), rest.len()),
+                [] => String::new(),
+            };
+            text!("        {} -> {}", input, entry.output_path.display());
+            for (name, path) in entry.inputs.iter() {
+                text!("        {}: {}", name, path.display());
+            }
+            let output_size = match entry.output_size {
+                Some(output_size) => format!(", {}", format_size(output_size)),
+                None => String::new(),
+            };
+            text!("        {:.1}s{}", entry.duration, output_size);
+            if let Some(quality) = &entry.quality {
+                text!("        {}", quality.summary());
+            }
+            if let Some(log_path) = &entry.log_path {
+                text!("        log: {}", log_path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs job `number` of the history again, on `input` instead of the original input when given.
+    fn rerun(&self, number: usize, input: Option<&Path>) -> Result<(), Error> {
+        let entries = history::load().map_err(Error::Other)?;
+        let entry = &entries[number - 1];
+        let command = self.config.find_command(&entry.hash).unwrap();
+        self.report_command(command);
+        self.print_message(
+            format!("Rerunning job {}: {}", number, command.title.bold()).as_str(),
+            true,
+        );
+        let output_path = match input {
+            Some(input) => command.default_output_path(input)?,
+            None => entry.output_path.clone(),
+        };
+        // `--var` overrides the values the job was run with.
+        let mut vars = command.variables.clone();
+        vars.extend(entry.vars.clone());
+        vars.extend(self.var_args());
+        let input_paths = match input {
+            Some(dir) if command.concat => concat::list_directory(dir, &command.first_input())?,
+            Some(input) => vec![input.to_path_buf()],
+            None => entry.input_paths.clone(),
+        };
+        let request = JobRequest {
+            command,
+            input_paths,
+            inputs: entry
+                .inputs
+                .iter()
+                .map(|(name, path)| (name.clone(), path.clone()))
+                .collect(),
+            // The conditions are evaluated again, for a new input in particular. Older entries
+            // only have the options whose conditions held, as flags and values.
+            options: entry.command_options.clone().unwrap_or_else(|| {
+                entry
+                    .options
+                    .chunks(2)
+                    .map(|option| super::CommandOption {
+                        flag: option[0].clone(),
+                        value: option.get(1).cloned().unwrap_or_default(),
+                        when: None,
+                    })
+                    .collect()
+            }),
+            vars,
+            output_path,
+            target_size: self.default_target_size(command),
+        };
+        self.execute_job(request, false).result
+    }
+
+    /// Emits a `command` event for the command that is going to run.
+    fn report_command(&self, command: &super::Command) {
+        if output::is_json() {
+            output::event(
+                "command",
+                json!({
+                    "hash": super::get_hash(command.title.clone()),
+                    "title": command.title,
+                }),
             );
+        }
+    }
+
+    fn ffmpeg_path(&self) -> &str {
+        if self.should_use_ffmpeg_path_field {
+            self.config.ffmpeg_path.as_str()
         } else {
-            let command: &super::Command = self.get_command();
-            let input_path = self.get_input_path();
-            let options = self.get_options(command.options.clone());
-            let output_path = self.get_output_path(input_path.clone(), command);
-            self.execute_command(command, input_path, options, output_path);
+            "ffmpeg"
         }
     }
 
+    /// The first input and the named inputs given by `--input_path` and `--input`.
+    fn input_args(&self, command: &super::Command) -> (PathBuf, HashMap<String, PathBuf>) {
+        let inputs: HashMap<String, PathBuf> = self
+            .args
+            .named_inputs()
+            .into_iter()
+            .filter_map(|input| input.ok())
+            .collect();
+        let input_path = match &self.args.input_path {
+            Some(input_path) => PathBuf::from(input_path),
+            None => inputs[&command.inputs[0].name].clone(),
+        };
+        (input_path, inputs)
+    }
+
+    fn ffprobe_path(&self) -> String {
+        self.config.ffprobe_path(self.should_use_ffmpeg_path_field)
+    }
+
+    /// Shows `message` to the user. In JSON mode stdout only has events, so it goes to stderr.
     fn print_message(&self, message: &str, is_from_system: bool) {
-        if is_from_system {
+        if output::is_json() {
+            eprintln!("{}", message);
+        } else if is_from_system {
             println!("[{}] {}", "SYSTEM".yellow(), message);
         } else {
             println!("[ {} ] {}", "USER".blue(), message);
         }
     }
 
-    fn unquote_string(&self, string: &str) -> String {
-        let re = Regex::new(r#"^['"](.*?)['"]$"#).unwrap();
-
-        if let Some(captures) = re.captures(string) {
-            return captures[1].to_string();
-        } else {
-            return string.to_string();
+    /// Reads an answer, and quits when it is `q`.
+    fn get_user_input_as_string(&self, message: &str) -> Result<String, Error> {
+        let input = self.get_user_input_as_text(message)?;
+        if input == "q" {
+            self.print_message("You chose to quit.", false);
+            return Err(Error::Aborted);
         }
+        Ok(input)
     }
 
-    fn get_user_input_as_string(&self, message: &str) -> String {
+    /// Reads a free-text answer such as the value of an option, where `q` is a literal value.
+    fn get_user_input_as_text(&self, message: &str) -> Result<String, Error> {
         print!("{} > ", message.bright_cyan());
         std::io::stdout().flush().unwrap();
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
+            Ok(0) => {
+                text!();
+                Err(Error::Other(
+                    "Reached the end of input before an answer was given.".to_string(),
+                ))
+            }
             Ok(_) => {
                 self.print_message(format!("You typed {}", input.trim().bold()).as_str(), false);
-                return input.trim().to_string();
+                Ok(input.trim().to_string())
             }
-            Err(error) => {
-                self.print_message(format!("error: {}", error.to_string()).as_str(), true);
-                panic!();
+            Err(error) => Err(Error::Other(error.to_string())),
+        }
+    }
+
+    fn get_user_input_as_usize(&self, message: &str, len: usize) -> Result<usize, Error> {
+        loop {
+            match self.get_user_input_as_string(message)?.parse::<usize>() {
+                Ok(value) if value < len => return Ok(value),
+                _ => self.print_message(
+                    format!(
+                        "Please type a number from 0 to {}, or 'q' to quit.",
+                        len.saturating_sub(1)
+                    )
+                    .as_str(),
+                    true,
+                ),
             }
         }
     }
 
-    fn get_user_input_as_usize(&self, message: &str) -> usize {
-        print!("{} > ", message.bright_cyan());
-        std::io::stdout().flush().unwrap();
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => match input.trim().parse::<usize>() {
-                Ok(value) => {
+    fn get_user_input_as_bool(&self, message: &str) -> Result<bool, Error> {
+        loop {
+            match self.get_user_input_as_string(message)?.as_str() {
+                "y" => return Ok(true),
+                "n" => return Ok(false),
+                _ => self.print_message("Please type 'y' or 'n', or 'q' to quit.", true),
+            }
+        }
+    }
+
+    fn get_command(&self) -> Result<&super::Command, Error> {
+        let command = self.choose_command(self.config.commands_with_tags(&self.args.tag))?;
+        self.print_message(format!("You chose {}", command.title).as_str(), false);
+        if let Some(description) = &command.description {
+            for line in description.lines() {
+                text!("    {}", line);
+            }
+        }
+        text!();
+        Ok(command)
+    }
+
+    fn choose_command<'a>(
+        &self,
+        commands: Vec<&'a super::Command>,
+    ) -> Result<&'a super::Command, Error> {
+        if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            match picker::pick_command(&commands, self.ffmpeg_path()) {
+                Ok(Pick::Selected(idx)) => return Ok(commands[idx]),
+                Ok(Pick::Cancelled) => {
+                    self.print_message("You chose to quit.", false);
+                    return Err(Error::Aborted);
+                }
+                Err(error) => {
                     self.print_message(
-                        format!("You typed {}", value.to_string().bold()).as_str(),
-                        false,
+                        format!("error: {}. Falling back to the numbered list.", error).as_str(),
+                        true,
                     );
-                    return value;
                 }
-                Err(_) => {
-                    self.print_message("error: Please type a number.", true);
-                    panic!();
+            }
+        }
+
+        self.print_message("Choose a command", true);
+        let mut numbered = Vec::new();
+        for (group, members) in super::group_commands(&commands) {
+            let indent = match group {
+                Some(group) => {
+                    text!("    {}", group.bold());
+                    "        "
                 }
-            },
+                None => "    ",
+            };
+            for command in members {
+                text!(
+                    "{}{}: {}",
+                    indent,
+                    numbered.len().to_string().green(),
+                    command.title
+                );
+                numbered.push(command);
+            }
+        }
+        Ok(numbered[self.get_user_input_as_usize("index", numbered.len())?])
+    }
+
+    fn read_path(&self, message: &str) -> Result<String, Error> {
+        if !io::stdin().is_terminal() {
+            return self.get_user_input_as_string(message);
+        }
+        let line = match PathReader::new().and_then(|mut reader| {
+            reader.read_line(format!("{} > ", message.bright_cyan()).as_str())
+        }) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => return Err(Error::Interrupted),
+            Err(ReadlineError::Eof) => {
+                return Err(Error::Other(
+                    "Reached the end of input before an answer was given.".to_string(),
+                ))
+            }
             Err(error) => {
-                self.print_message(format!("error: {}", error.to_string()).as_str(), true);
-                panic!();
+                self.print_message(format!("error: {}", error).as_str(), true);
+                return self.get_user_input_as_string(message);
             }
+        };
+        self.print_message(format!("You typed {}", line.trim().bold()).as_str(), false);
+        if line.trim() == "q" {
+            self.print_message("You chose to quit.", false);
+            return Err(Error::Aborted);
         }
+        Ok(line.trim().to_string())
     }
 
-    fn get_command(&self) -> &super::Command {
-        self.print_message("Choose a command", true);
-        for (idx, command) in self.config.commands.iter().enumerate() {
-            println!("    {}: {}", idx.to_string().green(), command.title);
+    fn get_input_paths(&self, input: &super::Input) -> Result<Vec<PathBuf>, Error> {
+        let message = format!(
+            "{} Multiple paths are processed as a batch.",
+            input.prompt()
+        );
+        loop {
+            self.print_message(message.as_str(), true);
+            let input_paths = paths::parse_paths(self.read_path("path")?.as_str());
+            if input_paths.is_empty() {
+                self.print_message("Please input a path.", true);
+                text!();
+                continue;
+            }
+            let problems: Vec<String> = input_paths
+                .iter()
+                .filter_map(|path| self.input_path_problem(path, input))
+                .collect();
+            if problems.is_empty() {
+                self.remember_paths(&input_paths);
+                text!();
+                return Ok(input_paths);
+            }
+            for problem in problems {
+                self.print_message(problem.as_str(), false);
+            }
+            text!();
         }
-        let command = &self.config.commands[self.get_user_input_as_usize("index")];
-        self.print_message(format!("You chose {}", command.title).as_str(), false);
-        println!();
-        return command;
     }
 
-    fn get_input_path(&self) -> PathBuf {
-        self.print_message("Input the path of the video file.", true);
-        let binding = self.unquote_string(self.get_user_input_as_string("path").as_str());
-        let input_path = Path::new(binding.as_str());
-        if input_path.is_file() {
-            println!();
-            return input_path.to_path_buf();
-        } else {
+    /// Describes why `path` cannot be used for `input`. The streams are checked with ffprobe
+    /// when `input` requires some, and the path is accepted with a warning when ffprobe fails.
+    fn input_path_problem(&self, path: &Path, input: &super::Input) -> Option<String> {
+        if !path.is_file() {
+            return Some(format!(
+                "{} is not a file.",
+                path.display().to_string().bold()
+            ));
+        }
+        if !input.accepts(path) {
+            return Some(format!(
+                "{} is not one of {}.",
+                path.display().to_string().bold(),
+                input.extensions.join(", ")
+            ));
+        }
+        if input.requires.is_empty() {
+            return None;
+        }
+        match self.jobs.probe(path) {
+            Ok(probe_result) => {
+                let missing = input.missing_streams(&probe_result);
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some(format!(
+                        "{} has no {} stream.",
+                        path.display().to_string().bold(),
+                        missing.join(" or ")
+                    ))
+                }
+            }
+            Err(error) => {
+                self.print_message(
+                    format!(
+                        "warning: Unable to check the streams of {}: {}",
+                        path.display(),
+                        error
+                    )
+                    .as_str(),
+                    true,
+                );
+                None
+            }
+        }
+    }
+
+    fn remember_paths(&self, paths: &[PathBuf]) {
+        if let Err(error) = PathReader::new().and_then(|mut reader| reader.remember(paths)) {
             self.print_message(
-                format!("{} is not a file.", input_path.display().to_string().bold()).as_str(),
-                false,
+                format!("error: Unable to save path history: {}", error).as_str(),
+                true,
             );
-            println!();
-            return self.get_input_path();
         }
     }
 
-    fn get_options(&self, options: Vec<super::CommandOption>) -> Vec<String> {
-        self.print_message("Current options are as follows.", true);
-        for option in options.iter() {
-            println!("    {}: {}", option.flag, option.value);
+    /// Asks for each named input after the first one.
+    fn get_named_inputs(
+        &self,
+        command: &super::Command,
+    ) -> Result<HashMap<String, PathBuf>, Error> {
+        let mut inputs = HashMap::new();
+        for input in command.inputs.iter().skip(1) {
+            loop {
+                self.print_message(input.prompt().as_str(), true);
+                let line = self.read_path(input.name.as_str())?;
+                if line.is_empty() {
+                    self.print_message("Please input a path.", true);
+                    text!();
+                    continue;
+                }
+                let path = paths::parse_path(line.as_str());
+                match self.input_path_problem(&path, input) {
+                    Some(problem) => {
+                        self.print_message(problem.as_str(), false);
+                        text!();
+                    }
+                    None => {
+                        self.remember_paths(std::slice::from_ref(&path));
+                        text!();
+                        inputs.insert(input.name.clone(), path);
+                        break;
+                    }
+                }
+            }
         }
-        self.print_message("Is it OK? Please type 'y' or 'n'.", true);
-        let input = self.get_user_input_as_string("y/n");
-        if input == "y" {
-            self.print_message("You chose to use current options.", false);
-            println!();
-            let mut result = Vec::new();
-            for option in options {
-                result.push(option.flag);
-                result.push(option.value);
+        Ok(inputs)
+    }
+
+    /// Asks for the files to concatenate. Returns the path the output is named after, which is the
+    /// directory when one is given and the first file otherwise, along with the files in order.
+    fn get_concat_input_paths(
+        &self,
+        command: &super::Command,
+    ) -> Result<(PathBuf, Vec<PathBuf>), Error> {
+        let input = command.first_input();
+        loop {
+            self.print_message(
+                "Input the paths of the files to concatenate in order, or a directory that contains them.",
+                true,
+            );
+            let paths = paths::parse_paths(self.read_path("path")?.as_str());
+            let (source, input_paths) = match paths.as_slice() {
+                [] => {
+                    self.print_message("Please input a path.", true);
+                    text!();
+                    continue;
+                }
+                [dir] if dir.is_dir() => match concat::list_directory(dir, &input) {
+                    Ok(input_paths) => (dir.clone(), input_paths),
+                    Err(error) => {
+                        self.print_message(format!("error: {}", error).as_str(), true);
+                        text!();
+                        continue;
+                    }
+                },
+                [first, ..] => (first.clone(), paths.clone()),
+            };
+            let problems: Vec<String> = input_paths
+                .iter()
+                .filter_map(|path| self.input_path_problem(path, &input))
+                .collect();
+            for problem in problems.iter() {
+                self.print_message(problem.as_str(), false);
+            }
+            if !problems.is_empty() {
+                text!();
+                continue;
+            }
+            if input_paths.len() < 2 {
+                self.print_message("Please input at least two files to concatenate.", true);
+                text!();
+                continue;
+            }
+            self.remember_paths(&paths);
+            self.print_message("Files are joined in the following order.", true);
+            for (idx, path) in input_paths.iter().enumerate() {
+                text!("    {}: {}", idx.to_string().green(), path.display());
+            }
+            text!();
+            return Ok((source, input_paths));
+        }
+    }
+
+    /// Lets the user edit the values of the options of `command`, and of those of `encoder`
+    /// before them. Returns the command with the edited values.
+    fn get_options(
+        &self,
+        command: &super::Command,
+        encoder: Option<&str>,
+    ) -> Result<super::Command, Error> {
+        let mut command = command.clone();
+        let encoder_idx =
+            encoder.and_then(|name| command.encoders.iter().position(|e| e.name == name));
+        let encoder_len = encoder_idx.map_or(0, |idx| command.encoders[idx].options.len());
+        let len = encoder_len + command.options.len();
+        loop {
+            self.print_message("Current options are as follows.", true);
+            for idx in 0..len {
+                let (option, encoder) = match encoder_idx {
+                    Some(encoder_idx) if idx < encoder_len => {
+                        let encoder = &command.encoders[encoder_idx];
+                        (&encoder.options[idx], Some(encoder.name.as_str()))
+                    }
+                    _ => (&command.options[idx - encoder_len], None),
+                };
+                text!(
+                    "    {}: {} {}{}{}",
+                    idx.to_string().green(),
+                    option.flag,
+                    option.value,
+                    encoder.map_or(String::new(), |name| format!(" (for {})", name)
+                        .dimmed()
+                        .to_string()),
+                    option
+                        .when
+                        .as_ref()
+                        .map_or(String::new(), |when| format!(" (when {})", when)
+                            .dimmed()
+                            .to_string())
+                );
+            }
+            self.print_message("Is it OK? Please type 'y' or 'n'.", true);
+            if self.get_user_input_as_bool("y/n")? {
+                self.print_message("You chose to use current options.", false);
+                text!();
+                return Ok(command);
+            }
+            if len == 0 {
+                self.print_message("This command has no options to change.", true);
+                text!();
+                continue;
             }
-            return result;
-        } else if input == "n" {
             self.print_message("Please type an index which you want to change.", true);
-            let input_idx = self.get_user_input_as_usize("index");
-            let option = &options[input_idx];
+            let input_idx = self.get_user_input_as_usize("index", len)?;
+            let option = match encoder_idx {
+                Some(encoder_idx) if input_idx < encoder_len => {
+                    &mut command.encoders[encoder_idx].options[input_idx]
+                }
+                _ => &mut command.options[input_idx - encoder_len],
+            };
             self.print_message(
-                format!("You chose to change option {}", option.flag.bold(),).as_str(),
+                format!("You chose to change option {}", option.flag.bold()).as_str(),
                 false,
             );
-            let mut new_options = options.clone();
             self.print_message("Input new value", true);
-            new_options[input_idx] = super::CommandOption {
-                flag: option.flag.clone(),
-                value: self.get_user_input_as_string("value"),
-            };
-            println!();
-            return self.get_options(new_options);
-        } else {
-            self.print_message("Please type 'y' or 'n'.", true);
-            println!();
-            return self.get_options(options);
-        }
-    }
-
-    fn get_output_path(&self, input_path: PathBuf, command: &super::Command) -> PathBuf {
-        let output_path = input_path.parent().unwrap().join(
-            input_path
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string()
-                + command.output_filename_suffix.as_str()
-                + command.output_extension.as_str(),
+            option.value = self.get_user_input_as_text("value")?;
+            text!();
+        }
+    }
+
+    /// Asks whether to save the edited options, and writes them to the config file and to the
+    /// config of this session.
+    fn save_options(
+        &mut self,
+        command: &super::Command,
+        edited: &super::Command,
+    ) -> Result<(), Error> {
+        self.print_message(
+            "Options were edited. Do you want to save them to the config file?",
+            true,
         );
+        text!("    {}: do not save", "n".green());
+        text!("    {}: update \"{}\"", "u".green(), command.title);
+        text!("    {}: save as a new command", "s".green());
+        let config_path = super::get_config_path();
+        let input = loop {
+            let input = self.get_user_input_as_string("n/u/s")?;
+            if ["n", "u", "s"].contains(&input.as_str()) {
+                break input;
+            }
+            self.print_message("Please type 'n', 'u' or 's', or 'q' to quit.", true);
+        };
+        let title = if input == "n" {
+            self.print_message("You chose not to save the options.", false);
+            text!();
+            return Ok(());
+        } else if input == "u" {
+            command.title.clone()
+        } else {
+            self.get_new_command_title()?
+        };
+        let saved = super::Command {
+            title: title.clone(),
+            ..edited.clone()
+        };
+        let result = if input == "u" {
+            let mut result = Ok(());
+            if edited.options != command.options {
+                result = config_editor::update_command_options(
+                    &config_path,
+                    &command.title,
+                    &edited.options,
+                );
+            }
+            if result.is_ok() && edited.encoders != command.encoders {
+                result = config_editor::update_command_encoders(
+                    &config_path,
+                    &command.title,
+                    &edited.encoders,
+                );
+            }
+            result
+        } else {
+            config_editor::append_command(&config_path, &saved)
+        };
+
+        match result {
+            Ok(()) => {
+                self.print_message(
+                    format!(
+                        "Saved to {}. hash -> {}, title -> {}",
+                        config_path.display(),
+                        super::get_hash(title.clone()).bright_cyan(),
+                        title
+                    )
+                    .as_str(),
+                    true,
+                );
+                // The rest of the session runs with the saved command as well.
+                match self.config.commands.iter_mut().find(|c| c.title == title) {
+                    Some(existing) => *existing = saved,
+                    None => self.config.commands.push(saved),
+                }
+            }
+            Err(error) => self.print_message(format!("error: {}", error).as_str(), true),
+        }
+        text!();
+        Ok(())
+    }
+
+    fn get_new_command_title(&self) -> Result<String, Error> {
+        loop {
+            self.print_message("Input a title for the new command.", true);
+            let title = self.get_user_input_as_text("title")?;
+            let hash = super::get_hash(title.clone());
+            if title.is_empty() {
+                self.print_message("Title must not be empty.", true);
+            } else if self
+                .config
+                .commands
+                .iter()
+                .any(|command| super::get_hash(command.title.clone()) == hash)
+            {
+                self.print_message(
+                    format!("{} is already used by another command.", title.bold()).as_str(),
+                    true,
+                );
+            } else {
+                return Ok(title);
+            }
+        }
+    }
+
+    fn get_target_size(&self, default: &str) -> Result<u64, Error> {
+        loop {
+            self.print_message(
+                format!(
+                    "Input the target size of the output file, e.g. 25M. Leave it empty to use {}.",
+                    default.bold()
+                )
+                .as_str(),
+                true,
+            );
+            let input = self.get_user_input_as_string("size")?;
+            let size = if input.is_empty() {
+                default
+            } else {
+                input.as_str()
+            };
+            match parse_size(size) {
+                Some(size) => {
+                    text!();
+                    return Ok(size);
+                }
+                None => self.print_message(
+                    format!("{} is not a valid size.", size.bold()).as_str(),
+                    true,
+                ),
+            }
+        }
+    }
+
+    /// Asks whether to use `output_path`, or another path.
+    fn get_output_path(&self, output_path: PathBuf) -> Result<PathBuf, Error> {
         self.print_message(
             format!(
                 "Output path is {}",
@@ -197,150 +972,543 @@ impl Runner {
             true,
         );
         self.print_message("Is it OK?", true);
-        let input = self.get_user_input_as_string("y/n");
-        if input == "y" {
+        if self.get_user_input_as_bool("y/n")? {
             self.print_message("You chose to use current output path.", false);
-            println!();
-            return output_path;
+            text!();
+            Ok(output_path)
         } else {
             self.print_message("Input new output path", true);
-            let input = self.get_user_input_as_string("path");
-            println!();
-            return Path::new(input.as_str()).to_path_buf();
+            let input = self.read_path("path")?;
+            text!();
+            Ok(paths::parse_path(input.as_str()))
         }
     }
 
-    fn execute_command(
+    fn run_batch(
         &self,
         command: &super::Command,
-        input_path: PathBuf,
-        options: Vec<String>,
-        output_path: PathBuf,
-    ) {
-        let mut command_str = command.command.clone();
-        command_str = command_str
-            .iter()
-            .map(|s| {
-                s.replace(
-                    "{{ffmpeg_path}}",
-                    if self.should_use_ffmpeg_path_field {
-                        self.config.ffmpeg_path.as_str()
-                    } else {
-                        "ffmpeg"
-                    },
-                )
+        input_paths: Vec<PathBuf>,
+        inputs: &HashMap<String, PathBuf>,
+        options: Vec<super::CommandOption>,
+        target_size: Option<u64>,
+    ) -> Result<(), Error> {
+        let jobs: Vec<(PathBuf, PathBuf)> = input_paths
+            .into_iter()
+            .map(|input_path| {
+                let output_path = command.default_output_path(&input_path)?;
+                Ok((input_path, output_path))
             })
-            .collect();
-        command_str = command_str
-            .iter()
-            .map(|s| s.replace("{{input_path}}", input_path.display().to_string().as_str()))
-            .collect();
-
-        if let Some(index) = command_str.iter().position(|s| s == "{{options}}") {
-            command_str.splice(index..index + 1, options);
+            .collect::<Result<_, Error>>()?;
+        self.print_message("Batch jobs are as follows.", true);
+        for (idx, (input_path, output_path)) in jobs.iter().enumerate() {
+            text!(
+                "    {}: {} -> {}",
+                idx.to_string().green(),
+                input_path.display(),
+                output_path.display()
+            );
         }
+        self.print_message("Is it OK?", true);
+        if !self.get_user_input_as_bool("y/n")? {
+            self.print_message("You chose not to execute the commands.", false);
+            return Err(Error::Aborted);
+        }
+        self.print_message("You chose to execute the commands.", false);
+        text!();
 
-        command_str = command_str
-            .iter()
-            .map(|s| {
-                s.replace(
-                    "{{output_path}}",
-                    output_path.display().to_string().as_str(),
+        let mut failed = Vec::new();
+        for (idx, (input_path, output_path)) in jobs.iter().enumerate() {
+            self.print_message(
+                format!(
+                    "[{}/{}] {}",
+                    idx + 1,
+                    jobs.len(),
+                    input_path.display().to_string().bold()
                 )
-            })
-            .collect();
+                .as_str(),
+                true,
+            );
+            let request = JobRequest {
+                command,
+                input_paths: vec![input_path.clone()],
+                inputs: inputs.clone(),
+                options: options.clone(),
+                vars: self.vars(command),
+                output_path: output_path.clone(),
+                target_size,
+            };
+            let result = self.execute_job(request, false);
+            if let Err(Error::Interrupted) = result.result {
+                self.print_message("Batch was stopped.", true);
+                return Err(Error::Interrupted);
+            }
+            if let Err(error) = result.result {
+                self.print_message(format!("error: {}", error).as_str(), true);
+                failed.push((input_path, result.log_path));
+            }
+            text!();
+        }
 
-        self.print_message("Command is as follows.", true);
-        println!("{:?}", command_str);
+        self.print_message(
+            format!(
+                "Batch finished. {} succeeded, {} failed.",
+                jobs.len() - failed.len(),
+                failed.len()
+            )
+            .as_str(),
+            true,
+        );
+        for (input_path, log_path) in failed.iter() {
+            text!("    {}", input_path.display().to_string().red());
+            if let Some(log_path) = log_path {
+                text!("        log: {}", log_path.display());
+            }
+        }
+        text!();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::JobsFailed {
+                failed: failed.len(),
+                total: jobs.len(),
+            })
+        }
+    }
 
-        self.print_message("Is it OK?", true);
-        let input = self.get_user_input_as_string("y/n");
-        if input == "y" {
-            self.print_message("You chose to execute the command.", false);
-            println!("{}", command_str.join(" "));
-            let result = match ProcessCommand::new(command_str[0].clone())
-                .args(&command_str[1..])
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .output()
-            {
-                Ok(result) => result,
-                Err(error) => {
-                    self.print_message(format!("error: {}", error.to_string()).as_str(), true);
-                    panic!();
-                }
-            };
+    fn print_steps(&self, steps: &[ExpandedStep]) {
+        if output::is_json() {
+            for step in steps {
+                output::event("argv", json!({ "step": step.name, "argv": step.argv }));
+            }
+        } else if let [step] = steps {
+            self.print_message("Command is as follows.", true);
+            text!("{:?}", step.argv);
+        } else {
+            self.print_message("Steps are as follows.", true);
+            for (idx, step) in steps.iter().enumerate() {
+                text!(
+                    "    {}: {} {:?}",
+                    idx.to_string().green(),
+                    step.name.bold(),
+                    step.argv
+                );
+            }
+        }
+    }
 
-            if result.status.success() {
-                self.print_message("Command executed successfully.", true);
+    /// Shows the table of the jobs run so far, if any, and where the report is saved.
+    fn print_summary(&self) {
+        let reports = self.reports.borrow();
+        if reports.is_empty() {
+            return;
+        }
+        for line in report::render(&reports, report::ReportFormat::Markdown).lines() {
+            if line.is_empty() {
+                text!();
             } else {
-                self.print_message("Command failed.", true);
+                text!("    {}", line);
             }
-        } else {
-            self.print_message("You chose not to execute the command.", false);
+        }
+        if let Some(path) = &self.args.report {
+            self.print_message(
+                format!("Report is saved to {}", path.display()).as_str(),
+                true,
+            );
         }
     }
 
-    fn execute_command_no_interaction(
+    /// Adds the job to the reports, and rewrites the `--report` file so that it is up to date even
+    /// when watching is stopped with Ctrl+C.
+    fn record_report(
         &self,
-        command: &super::Command,
-        input_path: PathBuf,
-        options: Vec<String>,
-        output_path: PathBuf,
+        sources: &[PathBuf],
+        entry: &history::HistoryEntry,
+        error: Option<&Error>,
     ) {
-        let mut command_str = command.command.clone();
-        command_str = command_str
+        let input_size: Option<u64> = sources
             .iter()
-            .map(|s| {
-                s.replace(
-                    "{{ffmpeg_path}}",
-                    if self.should_use_ffmpeg_path_field {
-                        self.config.ffmpeg_path.as_str()
-                    } else {
-                        "ffmpeg"
-                    },
+            .map(|source| fs::metadata(source).ok().map(|metadata| metadata.len()))
+            .sum();
+        // The duration is shown in the summary of the text output and in the report, and the
+        // sources are mostly probed already while planning.
+        let duration: Option<f64> = if self.args.report.is_some() || !output::is_json() {
+            sources
+                .iter()
+                .map(|source| self.jobs.probe(source).ok()?.duration())
+                .sum()
+        } else {
+            None
+        };
+        self.reports.borrow_mut().push(report::JobReport {
+            input_paths: sources.to_vec(),
+            output_path: entry.output_path.clone(),
+            hash: entry.hash.clone(),
+            title: entry.title.clone(),
+            duration,
+            wall_time: entry.duration,
+            input_size,
+            output_size: entry.output_size,
+            compression_ratio: report::compression_ratio(input_size, entry.output_size)
+                .filter(|_| error.is_none()),
+            psnr: entry.quality.as_ref().and_then(|scores| scores.psnr),
+            ssim: entry.quality.as_ref().and_then(|scores| scores.ssim),
+            vmaf: entry.quality.as_ref().and_then(|scores| scores.vmaf),
+            succeeded: error.is_none(),
+            error: error.map(|error| error.to_string()),
+        });
+        let Some(path) = &self.args.report else {
+            return;
+        };
+        let format = report::ReportFormat::from_path(path).unwrap();
+        if let Err(error) = fs::write(path, report::render(&self.reports.borrow(), format)) {
+            self.print_message(
+                format!(
+                    "warning: Unable to write the report to {}: {}",
+                    path.display(),
+                    error
                 )
-            })
-            .collect();
-        command_str = command_str
-            .iter()
-            .map(|s| s.replace("{{input_path}}", input_path.display().to_string().as_str()))
-            .collect();
-
-        if let Some(index) = command_str.iter().position(|s| s == "{{options}}") {
-            command_str.splice(index..index + 1, options);
+                .as_str(),
+                true,
+            );
         }
+    }
 
-        command_str = command_str
-            .iter()
-            .map(|s| {
-                s.replace(
-                    "{{output_path}}",
-                    output_path.display().to_string().as_str(),
-                )
-            })
-            .collect();
+    /// Shows the scores with the size and bitrate of both files, labeled with `labels`, or emits
+    /// a `quality` event.
+    fn report_quality(&self, report: &quality::QualityReport, labels: [&str; 2]) {
+        if output::is_json() {
+            output::event("quality", serde_json::to_value(report).unwrap());
+            return;
+        }
+        self.print_message(
+            format!("Quality: {}", report.scores.summary().bold()).as_str(),
+            true,
+        );
+        for (label, stats) in labels.iter().zip([&report.reference, &report.distorted]) {
+            text!(
+                "    {:<9} {:>10}  {:>12}  {}",
+                label,
+                format_size(stats.size),
+                stats.bitrate.map_or("-".to_string(), format_bitrate),
+                stats.path.display()
+            );
+        }
+    }
 
-        self.print_message("Command is as follows.", true);
-        println!("{:?}", command_str);
+    fn compare(&self, reference: &Path, distorted: &Path) -> Result<(), Error> {
+        self.print_message("Measuring the quality.", true);
+        let report = quality::measure(
+            self.ffmpeg_path(),
+            self.ffprobe_path().as_str(),
+            reference,
+            distorted,
+        )
+        .map_err(Error::Other)?;
+        self.report_quality(&report, ["Reference", "Distorted"]);
+        Ok(())
+    }
 
-        let result = match ProcessCommand::new(command_str[0].clone())
-            .args(&command_str[1..])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .output()
-        {
-            Ok(result) => result,
+    /// Plans the job and runs it. An interactive job asks before running.
+    fn execute_job(&self, request: JobRequest, is_interactive: bool) -> JobResult {
+        let mut reporter = JobReporter {
+            runner: self,
+            is_interactive,
+            steps: Vec::new(),
+        };
+        let unplanned = (!self.args.dry_run).then(|| request.clone());
+        let plan = job::plan(&self.jobs, request, &mut reporter).and_then(|plan| {
+            self.print_steps(plan.steps());
+            if is_interactive {
+                self.confirm_execution()?;
+            }
+            Ok(plan)
+        });
+        let result = match plan {
+            Ok(plan) => job::run(&self.jobs, plan, &mut reporter),
             Err(error) => {
-                self.print_message(format!("error: {}", error.to_string()).as_str(), true);
-                panic!();
+                // Declining to run the job is not a failure of it.
+                if let (Some(request), false) = (&unplanned, matches!(error, Error::Aborted)) {
+                    job::record_failure(request, &error, &mut reporter);
+                }
+                JobResult {
+                    result: Err(error),
+                    log_path: None,
+                }
             }
         };
+        self.jobs.clear_probes();
+        result
+    }
 
-        if result.status.success() {
-            self.print_message("Command executed successfully.", true);
+    fn confirm_execution(&self) -> Result<(), Error> {
+        self.print_message("Is it OK?", true);
+        if self.get_user_input_as_bool("y/n")? {
+            self.print_message("You chose to execute the command.", false);
+            Ok(())
         } else {
-            self.print_message("Command failed.", true);
+            self.print_message("You chose not to execute the command.", false);
+            Err(Error::Aborted)
         }
     }
 }
+
+/// Shows what happens in a job as it is planned and run, or emits it as events in JSON mode, and
+/// asks the user when the job is interactive.
+struct JobReporter<'a> {
+    runner: &'a Runner,
+    is_interactive: bool,
+    /// The steps that are running.
+    steps: Vec<ExpandedStep>,
+}
+
+impl execute::Observer for JobReporter<'_> {
+    fn stdout(&mut self) -> Stdio {
+        // stdout carries the events in JSON mode, so whatever ffmpeg prints there goes to stderr.
+        if output::is_json() {
+            io::stderr().into()
+        } else {
+            Stdio::inherit()
+        }
+    }
+
+    fn step_started(&mut self, index: usize, step: &ExpandedStep) {
+        if output::is_json() {
+            output::event(
+                "step",
+                json!({ "step": step.name, "index": index + 1, "count": self.steps.len() }),
+            );
+        } else if self.steps.len() > 1 {
+            self.runner.print_message(
+                format!(
+                    "[Step {}/{}] {}",
+                    index + 1,
+                    self.steps.len(),
+                    step.name.bold()
+                )
+                .as_str(),
+                true,
+            );
+        }
+        text!("{}", step.argv.join(" "));
+        interrupt::set_child_running(true);
+    }
+
+    fn progress(&mut self, step: &ExpandedStep, progress: &execute::Progress) {
+        if output::is_json() {
+            output::progress(step.name.as_str(), progress);
+        }
+    }
+
+    fn step_finished(&mut self, index: usize, step: &ExpandedStep, exit_code: Option<i32>) {
+        interrupt::set_child_running(false);
+        if output::is_json() {
+            output::event(
+                "step_finished",
+                json!({ "step": step.name, "exit_code": exit_code }),
+            );
+        }
+        if exit_code == Some(0) || self.steps.len() == 1 {
+            return;
+        }
+        self.runner.print_message(
+            format!(
+                "Step {} failed. The remaining steps were skipped.",
+                step.name.bold()
+            )
+            .as_str(),
+            true,
+        );
+        for (report_idx, report_step) in self.steps.iter().enumerate() {
+            let status = match report_idx.cmp(&index) {
+                Ordering::Less => "done".green(),
+                Ordering::Equal => "failed".red(),
+                Ordering::Greater => "skipped".yellow(),
+            };
+            text!("    {}: {}", report_step.name, status);
+        }
+    }
+}
+
+impl job::Observer for JobReporter<'_> {
+    fn message(&mut self, message: &str) {
+        self.runner.print_message(message, true);
+    }
+
+    fn warning(&mut self, message: &str) {
+        self.runner
+            .print_message(format!("warning: {}", message).as_str(), true);
+    }
+
+    fn encoder_chosen(&mut self, encoder: &Encoder, unavailable: &[&str]) {
+        if output::is_json() {
+            output::event(
+                "encoder",
+                json!({ "name": encoder.name, "unavailable": unavailable }),
+            );
+            return;
+        }
+        if !unavailable.is_empty() {
+            self.runner.print_message(
+                format!("Not available in ffmpeg: {}", unavailable.join(", ")).as_str(),
+                true,
+            );
+        }
+        self.runner.print_message(
+            format!("Using encoder {}.", encoder.name.bold()).as_str(),
+            true,
+        );
+    }
+
+    fn accept_reencode(&mut self, mismatches: &[String]) -> Result<bool, Error> {
+        self.runner.print_message(
+            "Stream parameters differ, so the files cannot be joined without re-encoding.",
+            true,
+        );
+        for mismatch in mismatches.iter() {
+            text!("    {}", mismatch.red());
+        }
+        if !self.is_interactive {
+            return Ok(false);
+        }
+        self.runner.print_message(
+            "Do you want to re-encode them to match the first file?",
+            true,
+        );
+        if !self.runner.get_user_input_as_bool("y/n")? {
+            self.runner
+                .print_message("You chose not to execute the command.", false);
+            return Err(Error::Aborted);
+        }
+        self.runner.print_message("You chose to re-encode.", false);
+        text!();
+        Ok(true)
+    }
+
+    fn bitrate_planned(
+        &mut self,
+        duration: f64,
+        audio_bitrate: u64,
+        video_bitrate: u64,
+        target_size: u64,
+    ) {
+        self.runner.print_message(
+            format!(
+                "Duration {:.1}s, audio {}, video bitrate {} to fit in {}.",
+                duration,
+                format_bitrate(audio_bitrate),
+                format_bitrate(video_bitrate).bold(),
+                format_size(target_size)
+            )
+            .as_str(),
+            true,
+        );
+    }
+
+    fn attempt_started(&mut self, attempt: usize, video_bitrate: u64) {
+        self.runner.print_message(
+            format!(
+                "[Attempt {}/{}] video bitrate {}",
+                attempt,
+                job::TARGET_SIZE_ATTEMPTS,
+                format_bitrate(video_bitrate)
+            )
+            .as_str(),
+            true,
+        );
+    }
+
+    fn attempt_finished(&mut self, output_size: u64, target_size: u64) {
+        let message = if output_size <= target_size {
+            "within the target of"
+        } else {
+            "which exceeds the target of"
+        };
+        self.runner.print_message(
+            format!(
+                "Output size is {}, {} {}.",
+                format_size(output_size).bold(),
+                message,
+                format_size(target_size)
+            )
+            .as_str(),
+            true,
+        );
+    }
+
+    fn steps_started(&mut self, steps: &[ExpandedStep]) {
+        self.steps = steps.to_vec();
+    }
+
+    fn steps_finished(&mut self, result: &Result<(), Error>) {
+        match result {
+            Ok(()) if self.runner.args.dry_run => self
+                .runner
+                .print_message("Dry run, so nothing was executed.", true),
+            Ok(()) => self
+                .runner
+                .print_message("Command executed successfully.", true),
+            Err(_) => self.runner.print_message("Command failed.", true),
+        }
+    }
+
+    fn interrupted(&self) -> bool {
+        interrupt::was_interrupted()
+    }
+
+    fn output_verified(&mut self, checks: &[verify::Check]) {
+        if output::is_json() {
+            output::event("verify", json!({ "checks": checks }));
+            return;
+        }
+        self.runner
+            .print_message("Output is checked as follows.", true);
+        for check in checks.iter() {
+            let status = if check.ok { "OK".green() } else { "NG".red() };
+            text!("    [{}] {}", status, check.message);
+        }
+    }
+
+    fn quality_measured(&mut self, report: &quality::QualityReport) {
+        self.runner.report_quality(report, ["Input", "Output"]);
+    }
+
+    fn log_saved(&mut self, log: &JobLog, failed: bool) {
+        if failed {
+            let tail = log.tail(
+                self.runner
+                    .config
+                    .log_tail_lines
+                    .unwrap_or(DEFAULT_LOG_TAIL_LINES),
+            );
+            self.runner.print_message(
+                format!("Last {} lines of the log are as follows.", tail.len()).as_str(),
+                true,
+            );
+            for line in tail {
+                text!("    {}", line);
+            }
+        }
+        self.runner.print_message(
+            format!("Log is saved to {}", log.path().display()).as_str(),
+            true,
+        );
+    }
+
+    fn job_recorded(
+        &mut self,
+        sources: &[PathBuf],
+        entry: &history::HistoryEntry,
+        error: Option<&Error>,
+    ) {
+        self.runner.record_report(sources, entry, error);
+        if output::is_json() {
+            let mut fields = serde_json::to_value(entry).unwrap();
+            fields["error"] = json!(error.map(|error| error.to_string()));
+            output::event("job", fields);
+        }
+    }
+}
+
+fn format_bitrate(bits_per_second: u64) -> String {
+    format!("{} kbps", bits_per_second / 1000)
+}
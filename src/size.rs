@@ -0,0 +1,38 @@
+//! Sizes of files, as written in the config and shown to the user.
+
+/// Parses a size such as `25M`, `700K` or `1.5G` into bytes. `K`, `M` and `G` are powers of 1000
+/// and `Ki`, `Mi` and `Gi` are powers of 1024, in either case. A trailing `B` is allowed.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_lowercase();
+    let size = size.strip_suffix('b').unwrap_or(&size);
+    let (number, multiplier) = [
+        ("ki", 1024.0),
+        ("mi", 1024.0 * 1024.0),
+        ("gi", 1024.0 * 1024.0 * 1024.0),
+        ("k", 1e3),
+        ("m", 1e6),
+        ("g", 1e9),
+    ]
+    .iter()
+    .find_map(|(suffix, multiplier)| Some((size.strip_suffix(suffix)?, *multiplier)))
+    .unwrap_or((size, 1.0));
+    let number: f64 = number.trim().parse().ok()?;
+    if number > 0.0 {
+        Some((number * multiplier) as u64)
+    } else {
+        None
+    }
+}
+
+/// Formats `bytes` with a decimal unit, such as `1.50 MB`.
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.2} GB", bytes as f64 / 1e9)
+    } else if bytes >= 1_000_000 {
+        format!("{:.2} MB", bytes as f64 / 1e6)
+    } else if bytes >= 1_000 {
+        format!("{:.1} KB", bytes as f64 / 1e3)
+    } else {
+        format!("{} B", bytes)
+    }
+}
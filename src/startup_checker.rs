@@ -1,10 +1,19 @@
 use colored::Colorize;
+use serde_json::json;
 use std::fs;
-use std::io::Write;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command as ProcessCommand;
 
-use crate::get_hash;
+use crate::error::Error;
+use crate::{
+    concat, get_config_path, get_hash, group_commands, history, output, probe, STREAM_TYPES,
+};
+use kffmpeg::report::ReportFormat;
+use kffmpeg::size::parse_size;
+use kffmpeg::template::{self, Segment};
+use kffmpeg::TemplateEntry;
+use kffmpeg::{condition, encoders};
 
 pub struct StartupChecker {
     pub args: super::Args,
@@ -13,37 +22,80 @@ pub struct StartupChecker {
 }
 
 impl StartupChecker {
-    pub fn check(&mut self) -> bool {
-        let mut result = self.check_config();
-        self.load_config();
-        result = self.check_args() && result;
-        result = self.check_ffmpeg_executable() && result;
-        println!();
-        return result;
+    /// Runs every check and prints the result of each. The error is of the first check that failed.
+    pub fn check(&mut self) -> Result<(), Error> {
+        let config_path = get_config_path();
+        let mut results = vec![self.check_config().then_some(()).ok_or_else(|| {
+            Error::Config(format!(
+                "Config file was not found, so a new one was made at {}. Edit it and run kffmpeg again.",
+                config_path.display()
+            ))
+        })];
+        if let Err(error) = self.load_config() {
+            text!();
+            return Err(error);
+        }
+        results.push(
+            self.check_commands()
+                .then_some(())
+                .ok_or(Error::Config("Some commands are invalid.".to_string())),
+        );
+        results.push(
+            self.check_args()
+                .then_some(())
+                .ok_or(Error::Validation("Arguments are invalid.".to_string())),
+        );
+        results.push(
+            self.check_ffmpeg_executable()
+                .then_some(())
+                .ok_or(Error::FfmpegNotFound(
+                    self.config.as_ref().unwrap().ffmpeg_path.clone(),
+                )),
+        );
+        results.push(
+            self.check_input_streams()
+                .then_some(())
+                .ok_or(Error::Validation(
+                    "Input files do not have the required streams.".to_string(),
+                )),
+        );
+        text!();
+        results.into_iter().collect()
     }
 
     fn print_message(&self, message: &str, is_ok: bool) {
-        if is_ok {
+        if output::is_json() {
+            output::event("check", json!({ "ok": is_ok, "message": message }));
+        } else if is_ok {
             println!("[  {}  ] {}", "OK".green(), message);
         } else {
             println!("[  {}  ] {}", "NG".red(), message);
         }
     }
 
-    fn create_config(&self) {
-        let config_path = dirs::home_dir()
-            .unwrap()
-            .join(".config")
-            .join("kffmpeg")
-            .join("config.yaml");
+    fn create_config(&self) -> io::Result<()> {
+        let config_path = get_config_path();
         let yaml_str = r#"ffmpeg_path: /usr/bin/ffmpeg
 commands:
-  - title: Make video lighter by using h264_nvenc CQ 32
-    options:
-      - flag: -cq
-        value: 32
-      - flag: -c:v
-        value: h264_nvenc
+  - title: Make video lighter with H.264
+    description: Re-encode the video to reduce the file size, with a hardware encoder when there is one.
+    tags: [video, h264]
+    group: Encode
+    requires: [video]
+    options: []
+    encoders:
+      - name: h264_nvenc
+        options:
+          - flag: -cq
+            value: 32
+      - name: h264_qsv
+        options:
+          - flag: -global_quality
+            value: 32
+      - name: libx264
+        options:
+          - flag: -crf
+            value: 28
     output_extension: .mp4
     output_filename_suffix: _light
     command:
@@ -52,34 +104,37 @@ commands:
       - "{{input_path}}"
       - "{{options}}"
       - "{{output_path}}"
-  - title: Concat videos by getting txt file
+  - title: Concat videos
+    description: Join the selected videos, or the videos in a directory, without re-encoding.
+    tags: [concat]
+    group: Utility
     options:
-      - flag: -safe
-        value: 0
       - flag: -c
         value: copy
     output_extension: .mp4
     output_filename_suffix: _concat
+    concat: true
+    extensions: [.mp4, .mov, .mkv, .webm, .ts]
+    requires: [video]
     command:
       - "{{ffmpeg_path}}"
       - -f
       - concat
+      - -safe
+      - 0
       - -i
       - "{{input_path}}"
       - "{{options}}"
       - "{{output_path}}"
 "#;
-        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
-        let mut file = fs::File::create(config_path.clone()).unwrap();
-        file.write_all(yaml_str.as_bytes()).unwrap();
+        fs::create_dir_all(config_path.parent().unwrap())?;
+        let mut file = fs::File::create(config_path.clone())?;
+        file.write_all(yaml_str.as_bytes())
     }
 
+    #[allow(clippy::needless_return, clippy::to_string_in_format_args)]
     fn check_config(&self) -> bool {
-        let config_path = dirs::home_dir()
-            .unwrap()
-            .join(".config")
-            .join("kffmpeg")
-            .join("config.yaml");
+        let config_path = get_config_path();
         if Path::is_file(&config_path) {
             self.print_message(
                 format!("Config file found at {}", config_path.display().to_string()).as_str(),
@@ -87,58 +142,323 @@ commands:
             );
             return true;
         } else {
-            self.create_config();
-            self.print_message(
-                format!(
-                    "Config file was not found. -> make at {}",
-                    config_path.display()
-                )
-                .as_str(),
-                false,
-            );
+            match self.create_config() {
+                Ok(()) => self.print_message(
+                    format!(
+                        "Config file was not found. -> make at {}",
+                        config_path.display()
+                    )
+                    .as_str(),
+                    false,
+                ),
+                Err(error) => self.print_message(
+                    format!(
+                        "Config file was not found and unable to make at {}: {}",
+                        config_path.display(),
+                        error
+                    )
+                    .as_str(),
+                    false,
+                ),
+            }
             return false;
         }
     }
 
-    fn load_config(&mut self) {
-        let config_path = dirs::home_dir()
-            .unwrap()
-            .join(".config")
-            .join("kffmpeg")
-            .join("config.yaml");
-        let config_str = fs::read_to_string(config_path).expect("Unable to read file");
-        let config: super::Config = serde_yaml::from_str(&config_str).unwrap();
-        self.config = Some(config);
-
-        match &self.config {
-            Some(config) => {
-                self.print_message("Config loaded.", true);
-                for command in config.commands.iter() {
-                    println!(
-                        "    {} -> {}",
+    fn load_config(&mut self) -> Result<(), Error> {
+        match super::Config::load(&get_config_path()) {
+            Ok(config) => self.config = Some(config),
+            Err(error) => {
+                if let Error::Config(message) = &error {
+                    self.print_message(message.as_str(), false);
+                }
+                return Err(error);
+            }
+        }
+
+        if let Some(config) = &self.config {
+            self.print_message("Config loaded.", true);
+            let commands = config.commands_with_tags(&self.args.tag);
+            for (group, members) in group_commands(&commands) {
+                let indent = match group {
+                    Some(group) => {
+                        text!("    {}", group.bold());
+                        "        "
+                    }
+                    None => "    ",
+                };
+                for command in members {
+                    text!(
+                        "{}{} -> {}",
+                        indent,
                         get_hash(command.title.clone()).as_str().bright_cyan(),
                         command.title.clone().as_str(),
                     );
                 }
             }
-            None => {}
         }
+        Ok(())
+    }
+
+    fn check_commands(&self) -> bool {
+        let mut result = true;
+        for command in self.config.as_ref().unwrap().commands.iter() {
+            let mut problems = Vec::new();
+            if command.command.is_empty() == command.steps.is_empty() {
+                problems.push("Specify either command or steps.".to_string());
+            }
+            // target_size always encodes in two passes.
+            let mode = if command.target_size.is_some() {
+                "target_size"
+            } else {
+                "two_pass"
+            };
+            let is_two_pass = command.two_pass || command.target_size.is_some();
+            if is_two_pass && !command.steps.is_empty() {
+                problems.push(format!("{} cannot be used with steps.", mode));
+            }
+            if is_two_pass
+                && !command
+                    .command
+                    .iter()
+                    .any(|entry| entry.is_arg("{{output_path}}"))
+            {
+                problems.push(format!(
+                    "{} needs {{{{output_path}}}} as a separate element of command.",
+                    mode
+                ));
+            }
+            if command.concat && command.target_size.is_some() {
+                problems.push("target_size cannot be used with concat.".to_string());
+            }
+            let has_input_filters = !command.extensions.is_empty() || !command.requires.is_empty();
+            if !command.inputs.is_empty() && has_input_filters {
+                problems.push(
+                    "extensions and requires of a command with inputs must be set on the inputs."
+                        .to_string(),
+                );
+            }
+            let requires = command
+                .requires
+                .iter()
+                .chain(
+                    command
+                        .inputs
+                        .iter()
+                        .flat_map(|input| input.requires.iter()),
+                )
+                .chain(
+                    command
+                        .verify
+                        .iter()
+                        .flat_map(|verify| verify.streams.iter()),
+                );
+            for codec_type in requires {
+                if !STREAM_TYPES.contains(&codec_type.as_str()) {
+                    problems.push(format!(
+                        "{} is not a stream type. Use one of {}.",
+                        codec_type,
+                        STREAM_TYPES.join(", ")
+                    ));
+                }
+            }
+            if let Some(verify) = &command.verify {
+                if verify
+                    .duration_tolerance
+                    .is_some_and(|tolerance| tolerance < 0.0)
+                {
+                    problems.push("duration_tolerance of verify must not be negative.".to_string());
+                }
+                if verify.max_size_ratio.is_some_and(|ratio| ratio <= 0.0) {
+                    problems.push("max_size_ratio of verify must be positive.".to_string());
+                }
+            }
+            if command.concat && !command.inputs.is_empty() {
+                problems.push("inputs cannot be used with concat.".to_string());
+            }
+            for (idx, input) in command.inputs.iter().enumerate() {
+                if command.inputs[..idx].iter().any(|i| i.name == input.name) {
+                    problems.push(format!("Input name {} is used more than once.", input.name));
+                }
+            }
+            for (idx, encoder) in command.encoders.iter().enumerate() {
+                if command.encoders[..idx]
+                    .iter()
+                    .any(|e| e.name == encoder.name)
+                {
+                    problems.push(format!(
+                        "Encoder {} is listed more than once.",
+                        encoder.name
+                    ));
+                }
+            }
+            if !command.encoders.is_empty() {
+                // -c and -codec without a stream specifier are left alone, since they are also used
+                // for the audio stream.
+                for option in command.options.iter().filter(|option| {
+                    encoders::is_codec_flag(&option.flag, Some('v'))
+                        && !["-c", "-codec"].contains(&option.flag.as_str())
+                }) {
+                    problems.push(format!(
+                        "{} cannot be used in options with encoders, which set the video codec.",
+                        option.flag
+                    ));
+                }
+            }
+            let has_options = command
+                .command
+                .iter()
+                .chain(command.steps.iter().flat_map(|step| step.command.iter()))
+                .flat_map(TemplateEntry::args)
+                .any(|s| s.contains("{{options}}"));
+            if !command.encoders.is_empty() && !has_options {
+                problems.push("encoders needs {{options}} in the command.".to_string());
+            }
+            for when in command.conditions() {
+                if let Err(problem) = condition::check(when) {
+                    problems.push(problem);
+                }
+            }
+            // Elements of the templates, with the index of the step they belong to.
+            let templates = command
+                .command
+                .iter()
+                .flat_map(TemplateEntry::args)
+                .map(|s| (None, s))
+                .chain(command.steps.iter().enumerate().flat_map(|(idx, step)| {
+                    step.command
+                        .iter()
+                        .flat_map(TemplateEntry::args)
+                        .map(move |s| (Some(idx), s))
+                }));
+            for (step_idx, element) in templates {
+                let segments = match template::parse(element) {
+                    Ok(segments) => segments,
+                    Err(problem) => {
+                        problems.push(problem);
+                        continue;
+                    }
+                };
+                for segment in segments {
+                    let Segment::Placeholder(name) = segment else {
+                        continue;
+                    };
+                    if let Some(input) = name.strip_prefix("inputs.") {
+                        if !command.inputs.iter().any(|i| i.name == input) {
+                            problems.push(format!("Input {} is not defined in inputs.", input));
+                        }
+                    } else if let Some(step) = template::step_name(name.as_str()) {
+                        match step_idx {
+                            Some(idx) if command.steps[..idx].iter().any(|s| s.name == step) => {}
+                            Some(idx) => problems.push(format!(
+                                "Step {} refers to {}, which is not an earlier step.",
+                                command.steps[idx].name, step
+                            )),
+                            None => problems.push(format!(
+                                "command refers to step {}, but steps are not used.",
+                                step
+                            )),
+                        }
+                    } else if !template::PLACEHOLDERS.contains(&name.as_str()) {
+                        problems.push(format!("{{{{{}}}}} is not a placeholder.", name));
+                    }
+                }
+            }
+            if let Some(size) = &command.target_size {
+                if parse_size(size).is_none() {
+                    problems.push(format!("target_size {} is not a valid size.", size));
+                }
+            }
+            for (idx, step) in command.steps.iter().enumerate() {
+                if command.steps[..idx].iter().any(|s| s.name == step.name) {
+                    problems.push(format!("Step name {} is used more than once.", step.name));
+                }
+                // The output of a step is named after it in the temporary directory.
+                let mut components = Path::new(&step.name).components();
+                let is_file_name = matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(name)), None) if name == step.name.as_str()
+                );
+                if !is_file_name {
+                    problems.push(format!(
+                        "Step name {} must be a plain file name.",
+                        step.name
+                    ));
+                }
+            }
+            for problem in problems {
+                self.print_message(
+                    format!("{} -> {}", command.title.bold(), problem).as_str(),
+                    false,
+                );
+                result = false;
+            }
+        }
+        result
     }
 
     fn check_args(&self) -> bool {
-        if self.args.hash != None && self.args.input_path == None {
+        if let Some(size) = &self.args.target_size {
+            if parse_size(size).is_none() {
+                self.print_message(
+                    format!("--target-size {} is not a valid size.", size).as_str(),
+                    false,
+                );
+                return false;
+            }
+        }
+        if let Some(path) = &self.args.report {
+            if ReportFormat::from_path(path).is_none() {
+                self.print_message(
+                    format!(
+                        "--report {} must end with .csv, .json or .md.",
+                        path.display()
+                    )
+                    .as_str(),
+                    false,
+                );
+                return false;
+            }
+        }
+        for var in self.args.named_vars() {
+            if let Err(var) = var {
+                self.print_message(
+                    format!("--var {} must be in the form NAME=VALUE.", var).as_str(),
+                    false,
+                );
+                return false;
+            }
+        }
+        match &self.args.subcommand {
+            Some(super::Subcommand::Watch {
+                dir,
+                command,
+                output_dir,
+            }) => return self.check_watch_args(dir, command, output_dir.as_deref()),
+            Some(super::Subcommand::History { .. }) => return true,
+            Some(super::Subcommand::Rerun { number, input }) => {
+                return self.check_rerun_args(*number, input.as_deref())
+            }
+            Some(super::Subcommand::Compare {
+                reference,
+                distorted,
+            }) => return self.check_compare_args(reference, distorted),
+            None => {}
+        }
+        let has_input = self.args.input_path.is_some() || !self.args.inputs.is_empty();
+        if self.args.hash.is_some() && !has_input {
             self.print_message(
                 "You need to specify --input_path when you specify --hash.",
                 false,
             );
-            return false;
-        } else if self.args.hash == None && self.args.input_path != None {
+            false
+        } else if self.args.hash.is_none() && has_input {
             self.print_message(
-                "You need to specify --hash when you specify --input_path.",
+                "You need to specify --hash when you specify --input_path or --input.",
                 false,
             );
-            return false;
-        } else if self.args.hash != None && self.args.input_path != None {
+            false
+        } else if self.args.hash.is_some() && has_input {
             let mut result: bool;
             if self
                 .config
@@ -176,36 +496,449 @@ commands:
                 self.print_message("Specified hash code is not found in config file", false);
                 result = false;
             }
-            if Path::is_file(Path::new(self.args.input_path.clone().unwrap().as_str())) {
-                self.print_message("Specified input file is found", true);
-                result = true && result;
+            let command = self
+                .config
+                .as_ref()
+                .unwrap()
+                .commands
+                .iter()
+                .find(|c| Some(get_hash(c.title.clone())) == self.args.hash);
+            if let Some(command) = command.filter(|c| !c.inputs.is_empty()) {
+                return self.check_named_input_args(command) && result;
+            }
+            if !self.args.inputs.is_empty() {
+                self.print_message("Specified command does not have named inputs.", false);
+                return false;
+            }
+            let input_path = self.args.input_path.clone().unwrap();
+            if command.is_some_and(|c| c.concat) {
+                if Path::is_dir(Path::new(input_path.as_str())) {
+                    self.print_message("Specified input directory is found", true);
+                } else {
+                    self.print_message(
+                        "Specified input directory is not found. A concat command takes a directory.",
+                        false,
+                    );
+                    result = false;
+                }
+            } else if Path::is_file(Path::new(input_path.as_str())) {
+                match command.map(|c| c.first_input()) {
+                    Some(input) if !input.accepts(Path::new(input_path.as_str())) => {
+                        self.print_message(
+                            format!(
+                                "Specified input file is not one of {}",
+                                input.extensions.join(", ")
+                            )
+                            .as_str(),
+                            false,
+                        );
+                        result = false;
+                    }
+                    _ => self.print_message("Specified input file is found", true),
+                }
             } else {
                 self.print_message("Specified input file is not found", false);
-                result = false && result;
+                result = false;
             }
-            return result;
+            result
+        } else if self
+            .config
+            .as_ref()
+            .unwrap()
+            .commands_with_tags(&self.args.tag)
+            .is_empty()
+        {
+            self.print_message(
+                format!(
+                    "No command has all of the specified tags: {}",
+                    self.args.tag.join(", ")
+                )
+                .as_str(),
+                false,
+            );
+            false
+        } else if output::is_json() {
+            self.print_message(
+                "--output-format json needs --hash and --input_path, or a subcommand, since it cannot ask questions.",
+                false,
+            );
+            false
         } else {
             self.print_message("You did not specify --hash and --input_path. So, kffmpeg will run with user interaction.", true);
-            return true;
+            true
+        }
+    }
+
+    fn check_rerun_args(&self, number: usize, input: Option<&Path>) -> bool {
+        let entries = match history::load() {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.print_message(error.as_str(), false);
+                return false;
+            }
+        };
+        let entry = match number.checked_sub(1).and_then(|idx| entries.get(idx)) {
+            Some(entry) => entry,
+            None => {
+                self.print_message(
+                    format!(
+                        "Job {} is not found in the history, which has {} jobs",
+                        number,
+                        entries.len()
+                    )
+                    .as_str(),
+                    false,
+                );
+                return false;
+            }
+        };
+        let command = match self
+            .config
+            .as_ref()
+            .unwrap()
+            .commands
+            .iter()
+            .find(|c| get_hash(c.title.clone()) == entry.hash)
+        {
+            Some(command) => command,
+            None => {
+                self.print_message(
+                    format!(
+                        "Command of job {} ({}) is no longer in config file",
+                        number, entry.hash
+                    )
+                    .as_str(),
+                    false,
+                );
+                return false;
+            }
+        };
+        self.print_message(
+            format!(
+                "Job {} is found in the history. title -> {}",
+                number, command.title
+            )
+            .as_str(),
+            true,
+        );
+        match input {
+            Some(input) if command.concat && !input.is_dir() => {
+                self.print_message(
+                    "Specified input directory is not found. A concat command takes a directory.",
+                    false,
+                );
+                false
+            }
+            Some(input) if !command.concat && !input.is_file() => {
+                self.print_message("Specified input file is not found", false);
+                false
+            }
+            Some(input) if !command.concat && !command.first_input().accepts(input) => {
+                self.print_message(
+                    format!(
+                        "Specified input file is not one of {}",
+                        command.first_input().extensions.join(", ")
+                    )
+                    .as_str(),
+                    false,
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn check_compare_args(&self, reference: &Path, distorted: &Path) -> bool {
+        let mut result = true;
+        for (name, path) in [("Reference", reference), ("Distorted", distorted)] {
+            if Path::is_file(path) {
+                self.print_message(
+                    format!("{} file is found at {}", name, path.display()).as_str(),
+                    true,
+                );
+            } else {
+                self.print_message(
+                    format!("{} file is not found at {}", name, path.display()).as_str(),
+                    false,
+                );
+                result = false;
+            }
+        }
+        result
+    }
+
+    fn check_watch_args(&self, dir: &Path, hash: &str, output_dir: Option<&Path>) -> bool {
+        let mut result = true;
+        if self.args.hash.is_some() || self.args.input_path.is_some() {
+            self.print_message(
+                "--hash and --input_path cannot be used with watch. Use --command instead.",
+                false,
+            );
+            result = false;
+        }
+        if Path::is_dir(dir) {
+            self.print_message(
+                format!("Directory to watch is found at {}", dir.display()).as_str(),
+                true,
+            );
+        } else {
+            self.print_message(
+                format!("Directory to watch is not found at {}", dir.display()).as_str(),
+                false,
+            );
+            result = false;
+        }
+        // Outputs written to the watched directory would be picked up as new inputs.
+        if let Some(output_dir) = output_dir {
+            if output_dir
+                .canonicalize()
+                .is_ok_and(|output_dir| dir.canonicalize().is_ok_and(|dir| dir == output_dir))
+            {
+                self.print_message("--output-dir must not be the directory to watch.", false);
+                result = false;
+            }
+        }
+        let command = match self
+            .config
+            .as_ref()
+            .unwrap()
+            .commands
+            .iter()
+            .find(|c| get_hash(c.title.clone()) == hash)
+        {
+            Some(command) => command,
+            None => {
+                self.print_message("Specified hash code is not found in config file", false);
+                return false;
+            }
+        };
+        self.print_message(
+            format!(
+                "Specified hash code is found in config file. title -> {}",
+                command.title
+            )
+            .as_str(),
+            true,
+        );
+        if command.concat {
+            self.print_message("A concat command cannot be used with watch.", false);
+            return false;
+        }
+        // The watched files are the first input, and the other inputs are the same for every file.
+        let mut named_inputs = Vec::new();
+        for input in self.args.named_inputs() {
+            match input {
+                Ok(input) => named_inputs.push(input),
+                Err(input) => {
+                    self.print_message(
+                        format!("--input {} must be in the form NAME=PATH.", input).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+            }
+        }
+        for input in command.inputs.iter().skip(1) {
+            match named_inputs.iter().find(|(name, _)| name == &input.name) {
+                Some((_, path)) if path.is_file() && input.accepts(path) => {}
+                Some(_) => {
+                    self.print_message(
+                        format!("Specified input file for {} is not usable", input.name).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+                None => {
+                    self.print_message(
+                        format!("You need to specify --input {}=PATH.", input.name).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+            }
+        }
+        for (name, _) in named_inputs.iter() {
+            if !command
+                .inputs
+                .iter()
+                .skip(1)
+                .any(|input| &input.name == name)
+            {
+                self.print_message(
+                    format!("Input {} cannot be specified with watch", name).as_str(),
+                    false,
+                );
+                result = false;
+            }
+        }
+        result
+    }
+
+    /// Checks `--input_path` and `--input` against the named inputs of `command`.
+    fn check_named_input_args(&self, command: &super::Command) -> bool {
+        let mut result = true;
+        let mut inputs = Vec::new();
+        if let Some(input_path) = &self.args.input_path {
+            inputs.push((command.inputs[0].name.clone(), PathBuf::from(input_path)));
+        }
+        for input in self.args.named_inputs() {
+            match input {
+                Ok(input) => inputs.push(input),
+                Err(input) => {
+                    self.print_message(
+                        format!("--input {} must be in the form NAME=PATH.", input).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+            }
+        }
+        for (name, path) in inputs.iter() {
+            match command.inputs.iter().find(|input| &input.name == name) {
+                None => {
+                    self.print_message(
+                        format!("Specified command does not have input {}", name).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+                Some(_) if !path.is_file() => {
+                    self.print_message(
+                        format!("Specified input file for {} is not found", name).as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+                Some(input) if !input.accepts(path) => {
+                    self.print_message(
+                        format!(
+                            "Specified input file for {} is not one of {}",
+                            name,
+                            input.extensions.join(", ")
+                        )
+                        .as_str(),
+                        false,
+                    );
+                    result = false;
+                }
+                Some(_) => {
+                    self.print_message(
+                        format!("Specified input file for {} is found", name).as_str(),
+                        true,
+                    );
+                }
+            }
         }
+        for input in command.inputs.iter() {
+            if inputs
+                .iter()
+                .filter(|(name, _)| name == &input.name)
+                .count()
+                > 1
+            {
+                self.print_message(
+                    format!("Input {} is specified more than once.", input.name).as_str(),
+                    false,
+                );
+                result = false;
+            }
+            if !inputs.iter().any(|(name, _)| name == &input.name) {
+                self.print_message(
+                    format!("You need to specify --input {}=PATH.", input.name).as_str(),
+                    false,
+                );
+                result = false;
+            }
+        }
+        result
+    }
+
+    /// Checks with ffprobe that the input files given on the command line have the streams
+    /// their command requires, which are the files in the directory for a concat command. Files
+    /// are not rejected when ffprobe fails.
+    fn check_input_streams(&self) -> bool {
+        let config = self.config.as_ref().unwrap();
+        let command = match config
+            .commands
+            .iter()
+            .find(|c| Some(get_hash(c.title.clone())) == self.args.hash)
+        {
+            Some(command) => command,
+            None => return true,
+        };
+        let mut inputs: Vec<(super::Input, PathBuf)> = Vec::new();
+        match &self.args.input_path {
+            Some(dir) if command.concat => {
+                let input = command.first_input();
+                // A directory that cannot be listed is reported when the command runs.
+                for path in concat::list_directory(Path::new(dir), &input).unwrap_or_default() {
+                    inputs.push((input.clone(), path));
+                }
+            }
+            Some(input_path) => {
+                inputs.push((command.first_input(), PathBuf::from(input_path)));
+            }
+            None => {}
+        }
+        for (name, path) in self.args.named_inputs().into_iter().flatten() {
+            if let Some(input) = command.inputs.iter().find(|input| input.name == name) {
+                inputs.push((input.clone(), path));
+            }
+        }
+
+        let ffprobe_path = config.ffprobe_path(self.should_use_ffmpeg_path_field.unwrap());
+        let mut result = true;
+        for (input, path) in inputs
+            .iter()
+            .filter(|(input, _)| !input.requires.is_empty())
+        {
+            match probe::probe(ffprobe_path.as_str(), path) {
+                Ok(probe_result) => {
+                    let missing = input.missing_streams(&probe_result);
+                    let label = if command.concat {
+                        format!("Input file {}", path.display())
+                    } else if command.inputs.is_empty() {
+                        "Specified input file".to_string()
+                    } else {
+                        format!("Specified input file for {}", input.name)
+                    };
+                    if missing.is_empty() {
+                        self.print_message(
+                            format!("{} has {} stream", label, input.requires.join(" and "))
+                                .as_str(),
+                            true,
+                        );
+                    } else {
+                        self.print_message(
+                            format!("{} has no {} stream", label, missing.join(" or ")).as_str(),
+                            false,
+                        );
+                        result = false;
+                    }
+                }
+                Err(error) => self.print_message(
+                    format!(
+                        "Unable to check the streams of {}, so it is used as it is: {}",
+                        path.display(),
+                        error
+                    )
+                    .as_str(),
+                    true,
+                ),
+            }
+        }
+        result
     }
 
+    #[allow(clippy::needless_return)]
     fn check_ffmpeg_executable(&mut self) -> bool {
-        let result = ProcessCommand::new("ffmpeg")
-            .arg("-version")
-            .output()
-            .expect("failed to execute process");
-        if result.status.success() {
+        if is_executable("ffmpeg") {
             self.print_message("ffmpeg command found", true);
             self.should_use_ffmpeg_path_field = Some(false);
             return true;
         } else {
             self.print_message("ffmpeg command not found", false);
-            let result_2 = ProcessCommand::new(self.config.as_ref().unwrap().ffmpeg_path.clone())
-                .arg("-version")
-                .output()
-                .expect("failed to execute process");
-            if result_2.status.success() {
+            if is_executable(self.config.as_ref().unwrap().ffmpeg_path.as_str()) {
                 self.print_message(
                     format!(
                         "ffmpeg command found at {}",
@@ -231,3 +964,11 @@ commands:
         }
     }
 }
+
+/// Whether `ffmpeg_path -version` runs successfully. A missing executable is not an error.
+fn is_executable(ffmpeg_path: &str) -> bool {
+    ProcessCommand::new(ffmpeg_path)
+        .arg("-version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
@@ -0,0 +1,337 @@
+//! Expansion of the placeholders in command templates into the argv of each step.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::condition::{self, Variables};
+use crate::{Command, CommandOption, Step, TemplateEntry};
+
+/// A step of a command with its placeholders expanded. Commands without `steps` run as a single step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandedStep {
+    pub name: String,
+    pub argv: Vec<String>,
+}
+
+/// Placeholders other than `inputs.<name>` and `steps.<name>.output`.
+pub const PLACEHOLDERS: [&str; 4] = ["ffmpeg_path", "input_path", "output_path", "options"];
+
+#[cfg(windows)]
+const NULL_DEVICE: &str = "NUL";
+#[cfg(not(windows))]
+const NULL_DEVICE: &str = "/dev/null";
+
+/// A piece of an element of a command template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Text that is used as it is, where `{{{{` has become `{{`.
+    Literal(String),
+    /// The name between the braces of a placeholder, such as `input_path` or `inputs.logo`.
+    Placeholder(String),
+}
+
+/// Splits an element of a template into literal text and placeholders. `{{{{` stands for a
+/// literal `{{`, and a `{{` without a closing `}}` is an error.
+pub fn parse(element: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = element;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("{{{{") {
+            literal.push_str("{{");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{{") {
+            let end = after.find("}}").ok_or(format!(
+                "{{{{ is not closed in {}. Write {{{{{{{{ for a literal {{{{.",
+                element
+            ))?;
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(Segment::Placeholder(after[..end].to_string()));
+            rest = &after[end + 2..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+/// Escapes `{{` in `s` so that it is used as it is in a template.
+pub fn escape(s: &str) -> String {
+    s.replace("{{", "{{{{")
+}
+
+/// Expands the placeholders of `template` in a single pass, so that text put in by a placeholder
+/// is never expanded again. `{{options}}` as an element of its own becomes one element per
+/// option, and inside a longer element it becomes the options joined with spaces. An unknown
+/// placeholder, or an undefined input or step, is an error.
+pub fn expand_template(
+    template: &[String],
+    ffmpeg_path: &str,
+    input_path: &Path,
+    inputs: &HashMap<String, PathBuf>,
+    options: &[String],
+    output_path: &Path,
+    step_outputs: &HashMap<String, PathBuf>,
+) -> Result<Vec<String>, String> {
+    let mut argv = Vec::new();
+    for element in template {
+        let segments = parse(element)?;
+        if let [Segment::Placeholder(name)] = segments.as_slice() {
+            if name == "options" {
+                argv.extend(options.iter().cloned());
+                continue;
+            }
+        }
+        let mut arg = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Literal(text) => arg.push_str(text.as_str()),
+                Segment::Placeholder(name) => {
+                    let value = match name.as_str() {
+                        "ffmpeg_path" => ffmpeg_path.to_string(),
+                        "input_path" => input_path.display().to_string(),
+                        "output_path" => output_path.display().to_string(),
+                        "options" => options.join(" "),
+                        _ => {
+                            if let Some(input) = name.strip_prefix("inputs.") {
+                                inputs
+                                    .get(input)
+                                    .ok_or(format!("Input {} is not defined.", input))?
+                                    .display()
+                                    .to_string()
+                            } else if let Some(step) = step_name(name.as_str()) {
+                                step_outputs
+                                    .get(step)
+                                    .ok_or(format!("Step {} is not defined.", step))?
+                                    .display()
+                                    .to_string()
+                            } else {
+                                return Err(format!("{{{{{}}}}} is not a placeholder.", name));
+                            }
+                        }
+                    };
+                    arg.push_str(value.as_str());
+                }
+            }
+        }
+        argv.push(arg);
+    }
+    Ok(argv)
+}
+
+/// The step that a `steps.<name>.output` placeholder refers to.
+pub fn step_name(placeholder: &str) -> Option<&str> {
+    placeholder
+        .strip_prefix("steps.")
+        .and_then(|rest| rest.strip_suffix(".output"))
+}
+
+/// The arguments of the entries whose conditions hold with `variables`.
+pub fn resolve_entries(
+    entries: &[TemplateEntry],
+    variables: &Variables,
+) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for entry in entries {
+        if let Some(when) = entry.condition() {
+            if !condition::evaluate(when, variables)? {
+                continue;
+            }
+        }
+        args.extend(entry.args().iter().cloned());
+    }
+    Ok(args)
+}
+
+/// Flags and values of the options whose conditions hold with `variables`.
+pub fn option_args(
+    options: &[CommandOption],
+    variables: &Variables,
+) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for option in options {
+        if let Some(when) = &option.when {
+            if !condition::evaluate(when, variables)? {
+                continue;
+            }
+        }
+        args.push(option.flag.clone());
+        args.push(option.value.clone());
+    }
+    Ok(args)
+}
+
+/// What the placeholders of a command expand to, other than the outputs of the steps.
+#[derive(Debug, Clone, Copy)]
+pub struct StepValues<'a> {
+    pub ffmpeg_path: &'a str,
+    pub input_path: &'a Path,
+    /// Named inputs other than the first one, which is `input_path`.
+    pub inputs: &'a HashMap<String, PathBuf>,
+    pub options: &'a [String],
+    pub output_path: &'a Path,
+    /// Directory for the outputs of all but the last step, and for the passlog of two passes.
+    pub temp_dir: &'a Path,
+}
+
+/// Expands the command into the argv of each step, leaving out the entries whose conditions do
+/// not hold with `variables`. `{{output_path}}` of a step is its own output, which is placed in
+/// `temp_dir` for all but the last step, and is `output_path` for the last one.
+pub fn expand_steps(
+    command: &Command,
+    values: &StepValues,
+    variables: &Variables,
+) -> Result<Vec<ExpandedStep>, String> {
+    let StepValues {
+        ffmpeg_path,
+        input_path,
+        inputs,
+        options,
+        output_path,
+        temp_dir,
+    } = *values;
+    // Each step with the options that `{{options}}` expands to in it.
+    let steps: Vec<(Step, Vec<String>)> = if command.two_pass || command.target_size.is_some() {
+        two_pass_steps(command, options, temp_dir)
+    } else if command.steps.is_empty() {
+        vec![(
+            Step {
+                name: "main".to_string(),
+                command: command.command.clone(),
+                output_extension: None,
+            },
+            options.to_vec(),
+        )]
+    } else {
+        command
+            .steps
+            .iter()
+            .map(|step| (step.clone(), options.to_vec()))
+            .collect()
+    };
+
+    let mut step_outputs = HashMap::new();
+    for (idx, (step, _)) in steps.iter().enumerate() {
+        let step_output = if idx == steps.len() - 1 {
+            output_path.to_path_buf()
+        } else {
+            temp_dir.join(
+                step.name.clone()
+                    + step
+                        .output_extension
+                        .as_deref()
+                        .unwrap_or(command.output_extension.as_str()),
+            )
+        };
+        step_outputs.insert(step.name.clone(), step_output);
+    }
+
+    let mut inputs = inputs.clone();
+    if let Some(first) = command.inputs.first() {
+        inputs.insert(first.name.clone(), input_path.to_path_buf());
+    }
+
+    steps
+        .into_iter()
+        .map(|(step, options)| {
+            Ok(ExpandedStep {
+                argv: expand_template(
+                    &resolve_entries(&step.command, variables)?,
+                    ffmpeg_path,
+                    input_path,
+                    &inputs,
+                    &options,
+                    &step_outputs[&step.name],
+                    &step_outputs,
+                )?,
+                name: step.name,
+            })
+        })
+        .collect()
+}
+
+/// Generates both passes of a two-pass encode from the command's template, with the options of
+/// each pass. The passlog is written to `temp_dir`, so that parallel jobs never share it.
+fn two_pass_steps(
+    command: &Command,
+    options: &[String],
+    temp_dir: &Path,
+) -> Vec<(Step, Vec<String>)> {
+    let passlog = temp_dir.join("passlog").display().to_string();
+    // libx265 ignores -pass and takes the pass settings through -x265-params instead.
+    let is_x265 = options
+        .iter()
+        .chain(command.command.iter().flat_map(TemplateEntry::args))
+        .any(|s| s == "libx265");
+    let x265_params = options
+        .iter()
+        .position(|s| s == "-x265-params")
+        .map(|idx| idx + 1)
+        .filter(|idx| *idx < options.len());
+    (1..=2)
+        .map(|pass| {
+            let mut pass_options = options.to_vec();
+            let mut pass_args: Vec<String> = if is_x265 {
+                let params = format!("pass={}:stats={}", pass, escape_x265_param(&passlog));
+                match x265_params {
+                    // Only the last -x265-params counts, so the settings are added to the one in
+                    // the options.
+                    Some(idx) => {
+                        if !options[idx].is_empty() {
+                            pass_options[idx] = format!("{}:{}", options[idx], params);
+                        } else {
+                            pass_options[idx] = params;
+                        }
+                        Vec::new()
+                    }
+                    None => vec!["-x265-params".to_string(), escape(params.as_str())],
+                }
+            } else {
+                vec![
+                    "-pass".to_string(),
+                    pass.to_string(),
+                    "-passlogfile".to_string(),
+                    escape(passlog.as_str()),
+                ]
+            };
+            if pass == 1 {
+                pass_args.extend(
+                    ["-an", "-f", "null", "-y", NULL_DEVICE]
+                        .iter()
+                        .map(|s| s.to_string()),
+                );
+            } else {
+                pass_args.push("{{output_path}}".to_string());
+            }
+            let step = Step {
+                name: format!("pass{}", pass),
+                command: command
+                    .command
+                    .iter()
+                    .flat_map(|entry| {
+                        if entry.is_arg("{{output_path}}") {
+                            pass_args.iter().cloned().map(TemplateEntry::Arg).collect()
+                        } else {
+                            vec![entry.clone()]
+                        }
+                    })
+                    .collect(),
+                output_extension: None,
+            };
+            (step, pass_options)
+        })
+        .collect()
+}
+
+/// Escapes `value` for the value of a key in `-x265-params`, where `:` separates the keys, so that
+/// a path with a drive letter such as `C:\Temp` stays in one value.
+fn escape_x265_param(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(':', "\\:")
+}
@@ -0,0 +1,143 @@
+//! Checks of the output after ffmpeg exits successfully, which catch an empty or truncated output.
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::probe::{self, ProbeResult};
+use crate::size::format_size;
+use crate::Verify;
+
+/// Result of one check, such as `duration` with `Output is 10.0s long, and the input is 10.0s.`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, ok: bool, message: String) -> Check {
+        Check { name, ok, message }
+    }
+}
+
+/// Checks `output_path` against `verify`, comparing it with `sources`, the files the job was run
+/// on. The durations and sizes of several sources, as of a concat job, are added up. The checks
+/// stop at the first one that fails when the rest cannot be made, such as for an empty output.
+/// The sources are probed with `probe_source`, so that probes taken earlier in the job can be
+/// reused, and the output with `ffprobe_path`.
+pub fn verify_output(
+    verify: &Verify,
+    ffprobe_path: &str,
+    probe_source: &dyn Fn(&Path) -> Result<ProbeResult, String>,
+    sources: &[PathBuf],
+    output_path: &Path,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let output_size = match fs::metadata(output_path) {
+        Ok(metadata) if metadata.len() > 0 => metadata.len(),
+        Ok(_) => {
+            checks.push(Check::new("exists", false, "Output is empty.".to_string()));
+            return checks;
+        }
+        Err(error) => {
+            checks.push(Check::new(
+                "exists",
+                false,
+                format!("Unable to read {}: {}", output_path.display(), error),
+            ));
+            return checks;
+        }
+    };
+    checks.push(Check::new(
+        "exists",
+        true,
+        format!("Output is {}.", format_size(output_size)),
+    ));
+    let output_probe = match probe::probe(ffprobe_path, output_path) {
+        Ok(probe_result) => probe_result,
+        Err(error) => {
+            checks.push(Check::new("readable", false, error));
+            return checks;
+        }
+    };
+    checks.push(Check::new(
+        "readable",
+        true,
+        "ffprobe can read the output.".to_string(),
+    ));
+
+    if let Some(tolerance) = verify.duration_tolerance {
+        let input_duration: Option<f64> = sources
+            .iter()
+            .map(|source| probe_source(source).ok()?.duration())
+            .sum();
+        checks.push(match (input_duration, output_probe.duration()) {
+            (_, None) => Check::new(
+                "duration",
+                false,
+                "Unable to get the duration of the output.".to_string(),
+            ),
+            (None, Some(output_duration)) => Check::new(
+                "duration",
+                true,
+                format!(
+                    "Output is {:.1}s long. The duration of the input is unknown, so they are not compared.",
+                    output_duration
+                ),
+            ),
+            (Some(input_duration), Some(output_duration)) => Check::new(
+                "duration",
+                (output_duration - input_duration).abs() <= tolerance,
+                format!(
+                    "Output is {:.1}s long, and the input is {:.1}s. Up to {}s of difference is allowed.",
+                    output_duration, input_duration, tolerance
+                ),
+            ),
+        });
+    }
+
+    for codec_type in verify.streams.iter() {
+        let count = output_probe.streams_of_type(codec_type).count();
+        checks.push(Check::new(
+            "streams",
+            count > 0,
+            format!("Output has {} {} stream(s).", count, codec_type),
+        ));
+    }
+
+    let input_size: u64 = sources
+        .iter()
+        .filter_map(|source| fs::metadata(source).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    checks.push(if input_size == 0 {
+        Check::new(
+            "size",
+            true,
+            "Size of the input is unknown, so the sizes are not compared.".to_string(),
+        )
+    } else {
+        let ratio = output_size as f64 / input_size as f64;
+        let message = format!(
+            "Output is {:.0}% of the size of the input ({} -> {}).",
+            ratio * 100.0,
+            format_size(input_size),
+            format_size(output_size)
+        );
+        match verify.max_size_ratio {
+            Some(max_size_ratio) if ratio > max_size_ratio => Check::new(
+                "size",
+                false,
+                format!(
+                    "{} Up to {:.0}% is allowed.",
+                    message,
+                    max_size_ratio * 100.0
+                ),
+            ),
+            _ => Check::new("size", true, message),
+        }
+    });
+    checks
+}
@@ -0,0 +1,136 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::concat::natural_cmp;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the size of a file has to stay the same before it is considered completely written.
+const STABLE_DURATION: Duration = Duration::from_secs(3);
+
+/// Watches a directory and reports files put directly in it once they have stopped growing.
+pub struct StableFiles {
+    receiver: Receiver<notify::Result<Event>>,
+    // Dropping the watcher stops the notifications.
+    _watcher: RecommendedWatcher,
+    pending: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl StableFiles {
+    /// Starts watching `dir`. Files already in it are reported too.
+    pub fn new(dir: &Path) -> Result<StableFiles, String> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|error| error.to_string())?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|error| error.to_string())?;
+        let mut stable_files = StableFiles {
+            receiver,
+            _watcher: watcher,
+            pending: HashMap::new(),
+        };
+        for entry in fs::read_dir(dir)
+            .map_err(|error| error.to_string())?
+            .flatten()
+        {
+            stable_files.add(entry.path());
+        }
+        Ok(stable_files)
+    }
+
+    fn add(&mut self, path: PathBuf) {
+        let is_hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if path.is_file() && !is_hidden {
+            self.pending
+                .entry(path)
+                .or_insert((u64::MAX, Instant::now()));
+        }
+    }
+
+    fn handle(&mut self, event: notify::Result<Event>) -> Result<(), String> {
+        let event = event.map_err(|error| error.to_string())?;
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            for path in event.paths {
+                self.add(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks until at least one file has stopped growing, and returns those files in natural order.
+    /// `should_stop` is checked between polls, and `None` is returned when it is true.
+    pub fn next(&mut self, should_stop: impl Fn() -> bool) -> Result<Option<Vec<PathBuf>>, String> {
+        loop {
+            if should_stop() {
+                return Ok(None);
+            }
+            match self.receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(event) => {
+                    self.handle(event)?;
+                    // Take the rest of the queued events too, and check the files below even while
+                    // another file keeps being written.
+                    while let Ok(event) = self.receiver.try_recv() {
+                        self.handle(event)?;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("The directory is no longer watched.".to_string())
+                }
+            }
+
+            let now = Instant::now();
+            let mut ready = Vec::new();
+            self.pending.retain(|path, (size, changed_at)| {
+                let current_size = match fs::metadata(path) {
+                    Ok(metadata) => metadata.len(),
+                    // Removed or renamed before it was processed.
+                    Err(_) => return false,
+                };
+                if current_size != *size {
+                    *size = current_size;
+                    *changed_at = now;
+                    true
+                } else if now.duration_since(*changed_at) >= STABLE_DURATION {
+                    ready.push(path.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+            if !ready.is_empty() {
+                ready.sort_by(|a, b| {
+                    natural_cmp(
+                        a.file_name().unwrap().to_string_lossy().as_ref(),
+                        b.file_name().unwrap().to_string_lossy().as_ref(),
+                    )
+                });
+                return Ok(Some(ready));
+            }
+        }
+    }
+}
+
+/// Moves `path` into `dir`, adding a number to the file name when `dir` already has a file of that name.
+pub fn move_into(path: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut destination = dir.join(path.file_name().unwrap_or_default());
+    let mut idx = 1;
+    while destination.exists() {
+        destination = dir.join(format!("{}_{}{}", stem, idx, extension));
+        idx += 1;
+    }
+    fs::rename(path, &destination)
+        .map_err(|error| format!("Unable to move {}: {}", path.display(), error))?;
+    Ok(destination)
+}
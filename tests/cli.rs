@@ -0,0 +1,1062 @@
+//! Runs the kffmpeg binary against the fake ffmpeg and ffprobe in `tests/fake-bin`.
+#![cfg(unix)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"
+ffmpeg_path: ffmpeg
+commands:
+  - title: Encode
+    options:
+      - flag: -c:v
+        value: libx264
+    output_extension: .mp4
+    output_filename_suffix: _enc
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+  - title: Pipeline
+    options: []
+    output_extension: .mp4
+    output_filename_suffix: _pipe
+    steps:
+      - name: audio
+        output_extension: .wav
+        command: ["{{ffmpeg_path}}", -i, "{{input_path}}", -vn, "{{output_path}}"]
+      - name: mux
+        command: ["{{ffmpeg_path}}", -i, "{{input_path}}", -i, "{{steps.audio.output}}", "{{output_path}}"]
+  - title: Lighter
+    options:
+      - flag: -c:a
+        value: copy
+        when: input.audio_codec == 'aac'
+      - flag: -c:a
+        value: aac
+        when: input.audio_codec != 'aac'
+    output_extension: .mp4
+    output_filename_suffix: _light
+    command:
+      - "{{ffmpeg_path}}"
+      - -i
+      - "{{input_path}}"
+      - when: input.height > 720 && var.scale != 'no'
+        args: [-vf, "scale=-2:720"]
+      - "{{options}}"
+      - "{{output_path}}"
+  - title: Fallback
+    options:
+      - flag: -b:a
+        value: 128k
+    encoders:
+      - name: h264_nvenc
+        options:
+          - flag: -cq
+            value: 32
+      - name: libx264
+        options:
+          - flag: -crf
+            value: 28
+    output_extension: .mp4
+    output_filename_suffix: _fb
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+  - title: Checked
+    options: []
+    verify:
+      duration_tolerance: 0.5
+      streams: [video, audio]
+    output_extension: .mp4
+    output_filename_suffix: _chk
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{output_path}}"]
+  - title: Needs audio
+    requires: [audio]
+    options: []
+    output_extension: .mp4
+    output_filename_suffix: _audio
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{output_path}}"]
+  - title: Join
+    concat: true
+    options:
+      - flag: -c
+        value: copy
+    output_extension: .mp4
+    output_filename_suffix: _concat
+    command: ["{{ffmpeg_path}}", -f, concat, -safe, 0, -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+"#;
+
+/// A home directory with a config file, and a directory for the input files.
+struct Env {
+    home: TempDir,
+    files: TempDir,
+}
+
+impl Env {
+    fn new() -> Env {
+        Env::with_config(CONFIG)
+    }
+
+    fn with_config(config: &str) -> Env {
+        let home = TempDir::new().unwrap();
+        let config_dir = home.path().join(".config").join("kffmpeg");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.yaml"), config).unwrap();
+        Env {
+            home,
+            files: TempDir::new().unwrap(),
+        }
+    }
+
+    /// Creates an empty input file named `name`.
+    fn input(&self, name: &str) -> PathBuf {
+        let path = self.files.path().join(name);
+        fs::write(&path, "").unwrap();
+        path
+    }
+
+    fn ffmpeg_log(&self) -> PathBuf {
+        self.home.path().join("ffmpeg.log")
+    }
+
+    /// Runs kffmpeg with `args` and `stdin` as the answers to the prompts.
+    fn run(&self, args: &[&str], stdin: &str) -> Output {
+        self.run_with_env(args, stdin, &[])
+    }
+
+    /// Runs kffmpeg like [`Env::run`], with extra environment variables for the fake ffmpeg. The
+    /// working directory is the directory for the input files.
+    fn run_with_env(&self, args: &[&str], stdin: &str, vars: &[(&str, &str)]) -> Output {
+        self.spawn(args, stdin, vars).wait_with_output().unwrap()
+    }
+
+    /// Runs kffmpeg like [`Env::run`] with `dir` as the working directory.
+    fn run_in(&self, dir: &Path, args: &[&str], stdin: &str) -> Output {
+        self.spawn_in(dir, args, stdin, &[])
+            .wait_with_output()
+            .unwrap()
+    }
+
+    /// Starts kffmpeg like [`Env::run_with_env`] without waiting for it to exit.
+    fn spawn(&self, args: &[&str], stdin: &str, vars: &[(&str, &str)]) -> Child {
+        self.spawn_in(self.files.path(), args, stdin, vars)
+    }
+
+    /// Starts kffmpeg like [`Env::spawn`] with `dir` as the working directory.
+    fn spawn_in(&self, dir: &Path, args: &[&str], stdin: &str, vars: &[(&str, &str)]) -> Child {
+        let fake_bin = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fake-bin");
+        let path = format!(
+            "{}:{}",
+            fake_bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kffmpeg"))
+            .args(args)
+            .current_dir(dir)
+            .env("HOME", self.home.path())
+            .env("PATH", path)
+            .env("FAKE_FFMPEG_LOG", self.ffmpeg_log())
+            .envs(vars.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child
+    }
+
+    /// Sends SIGINT to `child` once it has started ffmpeg, as Ctrl+C in a terminal would.
+    fn interrupt_when_ffmpeg_runs(&self, child: &Child) {
+        let started = Instant::now();
+        while self.ffmpeg_runs().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(50));
+        }
+        Command::new("kill")
+            .args(["-INT", &child.id().to_string()])
+            .status()
+            .unwrap();
+    }
+
+    /// Arguments of each run of ffmpeg.
+    fn ffmpeg_runs(&self) -> Vec<Vec<String>> {
+        fs::read_to_string(self.ffmpeg_log())
+            .unwrap_or_default()
+            .lines()
+            .map(|line| line.split_terminator('\x1f').map(str::to_string).collect())
+            .collect()
+    }
+}
+
+fn hash(title: &str) -> String {
+    kffmpeg::get_hash(title.to_string())
+}
+
+fn path_str(path: &Path) -> String {
+    path.display().to_string()
+}
+
+#[test]
+fn runs_command_with_hash_and_input_path() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let output_path = env.files.path().join("clip_enc.mp4");
+    assert_eq!(
+        env.ffmpeg_runs(),
+        vec![vec![
+            "-i".to_string(),
+            path_str(&input),
+            "-c:v".to_string(),
+            "libx264".to_string(),
+            path_str(&output_path),
+        ]]
+    );
+    assert!(output_path.exists());
+}
+
+#[test]
+fn passes_output_of_a_step_to_the_next() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Pipeline"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let runs = env.ffmpeg_runs();
+    assert_eq!(runs.len(), 2);
+    let audio = runs[0].last().unwrap();
+    assert!(audio.ends_with(".wav"));
+    assert_eq!(runs[1][3], *audio);
+    assert_eq!(
+        *runs[1].last().unwrap(),
+        path_str(&env.files.path().join("clip_pipe.mp4"))
+    );
+}
+
+#[test]
+fn hostile_file_name_is_passed_as_it_is() {
+    let env = Env::new();
+    let input = env.input("{{output_path}} {{options}} it's $(rm -rf x) {{{{.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let output_path = env
+        .files
+        .path()
+        .join("{{output_path}} {{options}} it's $(rm -rf x) {{{{_enc.mp4");
+    assert_eq!(
+        env.ffmpeg_runs(),
+        vec![vec![
+            "-i".to_string(),
+            path_str(&input),
+            "-c:v".to_string(),
+            "libx264".to_string(),
+            path_str(&output_path),
+        ]]
+    );
+    assert!(output_path.exists());
+}
+
+#[test]
+fn unknown_placeholder_exits_with_3() {
+    let env = Env::with_config(&CONFIG.replace("{{input_path}}", "{{input}}"));
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("{{input}} is not a placeholder."));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn concat_of_the_current_directory_is_named_after_it() {
+    let env = Env::new();
+    // The output goes next to the directory, so the directory is nested to keep the output in
+    // the temporary directory.
+    let dir = env.files.path().join("clips");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a.mp4"), "").unwrap();
+    fs::write(dir.join("b.mp4"), "").unwrap();
+    let output = env.run_in(&dir, &["--hash", &hash("Join"), "--input-path", "."], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output_path = env
+        .files
+        .path()
+        .canonicalize()
+        .unwrap()
+        .join("clips_concat.mp4");
+    assert_eq!(
+        env.ffmpeg_runs()[0].last().unwrap(),
+        &path_str(&output_path)
+    );
+}
+
+#[test]
+fn concat_reencode_replaces_stream_copy_with_codecs() {
+    let env = Env::new();
+    env.input("a.mp4");
+    env.input("noaudio.mp4");
+    let output = env.run(&[], "6\na.mp4 noaudio.mp4\ny\ny\ny\ny\n");
+    assert_eq!(output.status.code(), Some(0));
+    let run = &env.ffmpeg_runs()[0];
+    assert!(run.contains(&"-filter_complex".to_string()));
+    assert!(run.windows(2).any(|w| w == ["-c:v", "libx264"]));
+    assert!(!run.contains(&"copy".to_string()));
+}
+
+#[test]
+fn step_referring_to_its_own_output_exits_with_3() {
+    let env = Env::with_config(&CONFIG.replace(
+        "-vn, \"{{output_path}}\"",
+        "-vn, \"{{steps.audio.output}}\"",
+    ));
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Pipeline"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("Step audio refers to audio, which is not an earlier step."));
+}
+
+#[test]
+fn video_codec_in_options_with_encoders_exits_with_3() {
+    let env = Env::with_config(&CONFIG.replace(
+        "      - flag: -b:a\n        value: 128k\n    encoders:",
+        "      - flag: -vcodec\n        value: libx265\n    encoders:",
+    ));
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Fallback"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("-vcodec cannot be used in options with encoders"));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn step_name_with_a_directory_exits_with_3() {
+    for name in ["../mux", "/tmp/mux", ".."] {
+        let env = Env::with_config(&CONFIG.replace("- name: mux", &format!("- name: {}", name)));
+        let input = env.input("clip.mp4");
+        let output = env.run(
+            &[
+                "--hash",
+                &hash("Pipeline"),
+                "--input-path",
+                &path_str(&input),
+            ],
+            "",
+        );
+        assert_eq!(output.status.code(), Some(3));
+        assert!(String::from_utf8_lossy(&output.stdout)
+            .contains(&format!("Step name {} must be a plain file name.", name)));
+    }
+}
+
+#[test]
+fn input_is_probed_once_per_job() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let probe_log = env.home.path().join("ffprobe.log");
+    let output = env.run_with_env(
+        &[
+            "--hash",
+            &hash("Lighter"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+        &[("FAKE_FFPROBE_LOG", &path_str(&probe_log))],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let probed = fs::read_to_string(&probe_log).unwrap();
+    assert_eq!(probed.lines().collect::<Vec<_>>(), [path_str(&input)]);
+}
+
+#[test]
+fn verify_reuses_the_probe_of_the_input() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let probe_log = env.home.path().join("ffprobe.log");
+    let output = env.run_with_env(
+        &[
+            "--hash",
+            &hash("Checked"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+        &[("FAKE_FFPROBE_LOG", &path_str(&probe_log))],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let probed = fs::read_to_string(&probe_log).unwrap();
+    let input_probes = probed.lines().filter(|line| *line == path_str(&input));
+    assert_eq!(input_probes.count(), 1);
+}
+
+#[test]
+fn conditions_follow_each_input() {
+    let env = Env::new();
+    let with_audio = env.input("clip.mp4");
+    let without_audio = env.input("noaudio.mp4");
+    for input in [&with_audio, &without_audio] {
+        let output = env.run(
+            &["--hash", &hash("Lighter"), "--input-path", &path_str(input)],
+            "",
+        );
+        assert_eq!(output.status.code(), Some(0));
+    }
+    let runs = env.ffmpeg_runs();
+    assert_eq!(
+        runs[0][2..6],
+        ["-vf", "scale=-2:720", "-c:a", "copy"].map(String::from)
+    );
+    assert_eq!(
+        runs[1][2..6],
+        ["-vf", "scale=-2:720", "-c:a", "aac"].map(String::from)
+    );
+}
+
+#[test]
+fn var_is_used_in_conditions() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--var",
+            "scale=no",
+            "--hash",
+            &hash("Lighter"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!env.ffmpeg_runs()[0].contains(&"-vf".to_string()));
+}
+
+#[test]
+fn target_size_replaces_quality_options_with_the_bitrate() {
+    let env = Env::with_config(
+        r#"
+ffmpeg_path: ffmpeg
+commands:
+  - title: Sized
+    target_size: 25m
+    options:
+      - flag: -crf
+        value: 23
+    encoders:
+      - name: libx264
+        options:
+          - flag: -cq
+            value: 32
+    output_extension: .mp4
+    output_filename_suffix: _sized
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+"#,
+    );
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &["--hash", &hash("Sized"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let runs = env.ffmpeg_runs();
+    assert_eq!(runs.len(), 2);
+    for run in runs {
+        assert!(run.contains(&"-b:v".to_string()));
+        assert!(!run.contains(&"-crf".to_string()));
+        assert!(!run.contains(&"-cq".to_string()));
+    }
+}
+
+#[test]
+fn rerun_evaluates_the_conditions_again_with_the_recorded_vars() {
+    let env = Env::new();
+    let with_audio = env.input("clip.mp4");
+    let without_audio = env.input("noaudio.mp4");
+    let output = env.run(
+        &[
+            "--var",
+            "scale=no",
+            "--hash",
+            &hash("Lighter"),
+            "--input-path",
+            &path_str(&with_audio),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let output = env.run(&["rerun", "1", &path_str(&without_audio)], "");
+    assert_eq!(output.status.code(), Some(0));
+    let runs = env.ffmpeg_runs();
+    assert!(runs[0].windows(2).any(|w| w == ["-c:a", "copy"]));
+    assert!(runs[1].windows(2).any(|w| w == ["-c:a", "aac"]));
+    assert!(!runs[1].contains(&"-vf".to_string()));
+}
+
+#[test]
+fn first_available_encoder_is_used() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let args = [
+        "--hash",
+        &hash("Fallback"),
+        "--input-path",
+        &path_str(&input),
+    ];
+    let output = env.run(&args, "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Not available in ffmpeg: h264_nvenc"));
+    let output = env.run_with_env(&args, "", &[("FAKE_FFMPEG_ENCODERS", "h264_nvenc libx264")]);
+    assert_eq!(output.status.code(), Some(0));
+    let runs = env.ffmpeg_runs();
+    assert_eq!(
+        runs[0][2..8],
+        ["-c:v", "libx264", "-crf", "28", "-b:a", "128k"].map(String::from)
+    );
+    assert_eq!(
+        runs[1][2..8],
+        ["-c:v", "h264_nvenc", "-cq", "32", "-b:a", "128k"].map(String::from)
+    );
+}
+
+#[test]
+fn no_available_encoder_exits_with_3() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run_with_env(
+        &[
+            "--hash",
+            &hash("Fallback"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+        &[("FAKE_FFMPEG_ENCODERS", "libx265")],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn failure_of_ffmpeg_exits_with_6() {
+    let env = Env::new();
+    let input = env.input("FAIL.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(env.ffmpeg_runs().len(), 1);
+}
+
+#[test]
+fn verified_output_succeeds() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Checked"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Output is checked as follows."));
+}
+
+#[test]
+fn truncated_or_incomplete_output_exits_with_6() {
+    let env = Env::new();
+    let clip = env.input("clip.mp4");
+    let args = ["--hash", &hash("Checked"), "--input-path", &path_str(&clip)];
+    let output = env.run_with_env(&args, "", &[("FAKE_FFMPEG_OUTPUT", "truncated")]);
+    assert_eq!(output.status.code(), Some(6));
+    let output = env.run_with_env(&args, "", &[("FAKE_FFMPEG_OUTPUT", "garbage")]);
+    assert_eq!(output.status.code(), Some(6));
+    let silent = env.input("noaudio.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Checked"),
+            "--input-path",
+            &path_str(&silent),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Output has 0 audio stream(s)."));
+}
+
+#[test]
+fn empty_output_fails_only_with_verify() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let args = ["--hash", &hash("Encode"), "--input-path", &path_str(&input)];
+    let output = env.run_with_env(&args, "", &[("FAKE_FFMPEG_OUTPUT", "")]);
+    assert_eq!(output.status.code(), Some(0));
+    let output = env.run_with_env(
+        &[&["--verify"], &args[..]].concat(),
+        "",
+        &[("FAKE_FFMPEG_OUTPUT", "")],
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Output is empty."));
+}
+
+#[test]
+fn measure_quality_records_the_scores() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let report = env.files.path().join("report.json");
+    let output = env.run_with_env(
+        &[
+            "--output-format",
+            "json",
+            "--measure-quality",
+            "--report",
+            &path_str(&report),
+            "--hash",
+            &hash("Encode"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+        &[("FAKE_FFMPEG_FILTERS", "psnr ssim libvmaf")],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let events: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let quality = events
+        .iter()
+        .find(|event| event["event"] == "quality")
+        .unwrap();
+    assert_eq!(quality["scores"]["vmaf"], 95.5);
+    assert_eq!(quality["distorted"]["size"], 4);
+    let job = events.iter().find(|event| event["event"] == "job").unwrap();
+    assert_eq!(job["quality"]["psnr"], 41.25);
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(report["jobs"][0]["psnr"], 41.25);
+    assert_eq!(report["jobs"][0]["vmaf"], 95.5);
+    let runs = env.ffmpeg_runs();
+    assert_eq!(runs.len(), 2);
+    assert!(runs[1].iter().any(|arg| arg.contains("libvmaf")));
+}
+
+#[test]
+fn compare_reports_the_scores() {
+    let env = Env::new();
+    let reference = env.input("clip.mp4");
+    let distorted = env.input("clip_enc.mp4");
+    let output = env.run(
+        &["compare", &path_str(&reference), &path_str(&distorted)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("PSNR 41.25 dB, SSIM 0.9833"));
+    assert!(!stdout.contains("VMAF"));
+    let missing = env.files.path().join("missing.mp4");
+    let output = env.run(&["compare", &path_str(&reference), &path_str(&missing)], "");
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn report_has_the_jobs_of_the_run() {
+    let env = Env::new();
+    let report = env.files.path().join("report.json");
+    for name in ["clip.mp4", "FAIL.mp4"] {
+        let input = env.input(name);
+        env.run(
+            &[
+                "--report",
+                &path_str(&report),
+                "--hash",
+                &hash("Encode"),
+                "--input-path",
+                &path_str(&input),
+            ],
+            "",
+        );
+    }
+    // The second run replaces the report of the first.
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let jobs = report["jobs"].as_array().unwrap();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0]["hash"], hash("Encode"));
+    assert_eq!(jobs[0]["succeeded"], false);
+    assert_eq!(jobs[0]["duration"], 10.0);
+    assert_eq!(report["total"]["failed"], 1);
+}
+
+#[test]
+fn summary_is_shown_after_a_single_job() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("| clip.mp4 | clip_enc.mp4 | Encode"));
+    assert!(stdout.contains("1 jobs, 1 succeeded, 0 failed"));
+}
+
+#[test]
+fn report_with_unknown_extension_exits_with_4() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--report",
+            "report.txt",
+            "--hash",
+            &hash("Encode"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn missing_input_exits_with_4() {
+    let env = Env::new();
+    let input = env.files.path().join("missing.mp4");
+    let output = env.run(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn input_without_required_stream_exits_with_4() {
+    let env = Env::new();
+    let input = env.input("noaudio.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Needs audio"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn concat_file_without_required_stream_exits_with_4() {
+    let env = Env::with_config(&CONFIG.replace(
+        "    concat: true\n",
+        "    concat: true\n    requires: [audio]\n",
+    ));
+    env.input("a.mp4");
+    env.input("noaudio.mp4");
+    let output = env.run(
+        &[
+            "--hash",
+            &hash("Join"),
+            "--input-path",
+            &path_str(env.files.path()),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn watch_with_the_watched_directory_as_output_exits_with_4() {
+    let env = Env::new();
+    let dir = path_str(env.files.path());
+    let output = env.run(
+        &[
+            "watch",
+            &dir,
+            "--command",
+            &hash("Encode"),
+            "--output-dir",
+            ".",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("--output-dir must not be the directory to watch."));
+}
+
+#[test]
+fn dry_run_does_not_run_ffmpeg() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--dry-run",
+            "--hash",
+            &hash("Encode"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("libx264"));
+    assert!(env.ffmpeg_runs().is_empty());
+    assert!(!env.files.path().join("clip_enc.mp4").exists());
+}
+
+#[test]
+fn json_output_emits_events() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[
+            "--output-format",
+            "json",
+            "--hash",
+            &hash("Encode"),
+            "--input-path",
+            &path_str(&input),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let events: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let names: Vec<&str> = events
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    for name in [
+        "check",
+        "command",
+        "step",
+        "progress",
+        "step_finished",
+        "job",
+    ] {
+        assert!(names.contains(&name), "{} is not in {:?}", name, names);
+    }
+    let job = events.iter().find(|event| event["event"] == "job").unwrap();
+    assert_eq!(job["succeeded"], true);
+    // Prose goes to stderr so that stdout only has structured events.
+    assert!(!names.contains(&"message"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Log is saved to"));
+}
+
+#[test]
+fn interactive_run_asks_and_executes() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(&[], &format!("0\n{}\ny\ny\ny\n", path_str(&input)));
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(env.ffmpeg_runs().len(), 1);
+    assert!(env.files.path().join("clip_enc.mp4").exists());
+}
+
+#[test]
+fn ctrl_c_during_a_batch_stops_it_and_exits_with_130() {
+    let env = Env::new();
+    let first = env.input("a.mp4");
+    let second = env.input("b.mp4");
+    let child = env.spawn(
+        &[],
+        &format!("0\n{} {}\ny\ny\n", path_str(&first), path_str(&second)),
+        &[("FAKE_FFMPEG_SLEEP", "2")],
+    );
+    env.interrupt_when_ffmpeg_runs(&child);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(env.ffmpeg_runs().len(), 1);
+}
+
+#[test]
+fn ctrl_c_during_a_single_job_exits_with_130() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let child = env.spawn(
+        &["--hash", &hash("Encode"), "--input-path", &path_str(&input)],
+        "",
+        &[("FAKE_FFMPEG_SLEEP", "2")],
+    );
+    env.interrupt_when_ffmpeg_runs(&child);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+}
+
+#[test]
+fn batch_job_failing_before_it_runs_is_in_the_report_and_the_history() {
+    let env = Env::new();
+    let good = env.input("a.mp4");
+    let unreadable = env.input("b.mp4");
+    fs::write(&unreadable, "garbage").unwrap();
+    let report = env.files.path().join("report.json");
+    let output = env.run(
+        &["--report", &path_str(&report)],
+        &format!("2\n{} {}\ny\ny\n", path_str(&good), path_str(&unreadable)),
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(env.ffmpeg_runs().len(), 1);
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let jobs = report["jobs"].as_array().unwrap();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[1]["succeeded"], false);
+    assert!(jobs[1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("Unable to evaluate the conditions"));
+    assert_eq!(report["total"]["failed"], 1);
+    let history = fs::read_to_string(
+        env.home
+            .path()
+            .join(".config")
+            .join("kffmpeg")
+            .join("history.jsonl"),
+    )
+    .unwrap();
+    let entries: Vec<Value> = history
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1]["succeeded"], false);
+    assert_eq!(entries[1]["input_paths"][0], path_str(&unreadable));
+}
+
+#[test]
+fn interactive_run_is_aborted_when_declined() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(&[], &format!("0\n{}\ny\ny\nn\n", path_str(&input)));
+    assert_eq!(output.status.code(), Some(7));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn options_of_the_chosen_encoder_are_edited_and_saved() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[],
+        &format!("3\n{}\nn\n0\n20\ny\nu\ny\ny\n", path_str(&input)),
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        env.ffmpeg_runs()[0][2..8],
+        ["-c:v", "libx264", "-crf", "20", "-b:a", "128k"]
+    );
+    let config: serde_yaml::Value = serde_yaml::from_str(
+        &fs::read_to_string(
+            env.home
+                .path()
+                .join(".config")
+                .join("kffmpeg")
+                .join("config.yaml"),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let fallback = &config["commands"][3];
+    assert_eq!(fallback["encoders"][0]["options"][0]["value"], "32");
+    assert_eq!(fallback["encoders"][1]["options"][0]["value"], "20");
+}
+
+#[test]
+fn quitting_at_a_prompt_exits_with_7() {
+    let env = Env::new();
+    let output = env.run(&[], "q\n");
+    assert_eq!(output.status.code(), Some(7));
+    assert!(env.ffmpeg_runs().is_empty());
+}
+
+#[test]
+fn q_is_a_value_at_the_value_prompt() {
+    let env = Env::new();
+    let input = env.input("clip.mp4");
+    let output = env.run(
+        &[],
+        &format!("0\n{}\nn\n0\nq\ny\nn\ny\ny\n", path_str(&input)),
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(env.ffmpeg_runs()[0][2..4], ["-c:v", "q"]);
+}
+
+#[test]
+fn closed_stdin_exits_with_1() {
+    let env = Env::new();
+    let output = env.run(&[], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn missing_config_is_created_and_exits_with_3() {
+    let env = Env::new();
+    fs::remove_dir_all(env.home.path().join(".config")).unwrap();
+    let output = env.run(&[], "");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(env
+        .home
+        .path()
+        .join(".config")
+        .join("kffmpeg")
+        .join("config.yaml")
+        .exists());
+}
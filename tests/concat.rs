@@ -0,0 +1,90 @@
+//! Orders and lists the files of a concat job.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::PathBuf;
+
+use kffmpeg::concat::{natural_cmp, write_list};
+
+#[test]
+fn orders_numbers_by_value() {
+    let mut names = vec!["clip10.mp4", "clip2.mp4", "clip1.mp4", "clip002b.mp4"];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(
+        names,
+        ["clip1.mp4", "clip2.mp4", "clip002b.mp4", "clip10.mp4"]
+    );
+}
+
+#[test]
+fn ignores_leading_zeros() {
+    assert_eq!(natural_cmp("clip007", "clip7"), Ordering::Equal);
+    assert_eq!(natural_cmp("clip007", "clip8"), Ordering::Less);
+}
+
+#[test]
+fn compares_numbers_longer_than_an_integer() {
+    assert_eq!(
+        natural_cmp(
+            "part99999999999999999999999",
+            "part100000000000000000000000"
+        ),
+        Ordering::Less
+    );
+}
+
+#[test]
+fn ignores_case() {
+    assert_eq!(natural_cmp("Clip1", "clip1"), Ordering::Equal);
+    assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+}
+
+#[test]
+fn puts_a_prefix_first() {
+    assert_eq!(natural_cmp("clip", "clip1"), Ordering::Less);
+    assert_eq!(natural_cmp("clip1", "clip"), Ordering::Greater);
+    assert_eq!(natural_cmp("", ""), Ordering::Equal);
+}
+
+#[test]
+fn writes_absolute_paths_in_single_quotes() {
+    let dir = tempfile::tempdir().unwrap();
+    let list_path = dir.path().join("concat.txt");
+    let paths = [dir.path().join("a.mp4"), dir.path().join("my clip.mp4")];
+    write_list(&paths, &list_path).unwrap();
+    assert_eq!(
+        fs::read_to_string(&list_path).unwrap(),
+        format!(
+            "ffconcat version 1.0\nfile '{}'\nfile '{}'\n",
+            paths[0].display(),
+            paths[1].display()
+        )
+    );
+}
+
+#[test]
+fn escapes_single_quotes() {
+    let dir = tempfile::tempdir().unwrap();
+    let list_path = dir.path().join("concat.txt");
+    let path = dir.path().join("it's 'quoted'.mp4");
+    write_list(std::slice::from_ref(&path), &list_path).unwrap();
+    let expected = path.display().to_string().replace('\'', r"'\''");
+    assert!(expected.ends_with(r"it'\''s '\''quoted'\''.mp4"));
+    assert_eq!(
+        fs::read_to_string(&list_path).unwrap(),
+        format!("ffconcat version 1.0\nfile '{}'\n", expected)
+    );
+}
+
+#[test]
+fn makes_relative_paths_absolute() {
+    let dir = tempfile::tempdir().unwrap();
+    let list_path = dir.path().join("concat.txt");
+    write_list(&[PathBuf::from("a.mp4")], &list_path).unwrap();
+    let list = fs::read_to_string(&list_path).unwrap();
+    let expected = std::env::current_dir().unwrap().join("a.mp4");
+    assert_eq!(
+        list,
+        format!("ffconcat version 1.0\nfile '{}'\n", expected.display())
+    );
+}
@@ -0,0 +1,109 @@
+//! Evaluation of `when:` conditions against probed inputs and user variables.
+
+use std::collections::BTreeMap;
+
+use kffmpeg::condition::{check, evaluate, Variables};
+use kffmpeg::probe::ProbeResult;
+
+fn probe_result(height: u64, audio_codec: Option<&str>) -> ProbeResult {
+    let mut streams = vec![serde_json::json!({
+        "codec_type": "video",
+        "codec_name": "h264",
+        "width": height * 16 / 9,
+        "height": height,
+        "r_frame_rate": "30000/1001",
+    })];
+    if let Some(audio_codec) = audio_codec {
+        streams.push(serde_json::json!({
+            "codec_type": "audio",
+            "codec_name": audio_codec,
+            "bit_rate": "128000",
+        }));
+    }
+    serde_json::from_value(serde_json::json!({
+        "streams": streams,
+        "format": {"duration": "10.0"},
+    }))
+    .unwrap()
+}
+
+fn variables(height: u64, audio_codec: Option<&str>) -> Variables {
+    let vars = BTreeMap::from([("mode".to_string(), "fast".to_string())]);
+    Variables::new()
+        .with_input(&probe_result(height, audio_codec))
+        .with_vars(&vars)
+}
+
+#[test]
+fn compares_numbers() {
+    let hd = variables(1080, Some("aac"));
+    let sd = variables(720, Some("aac"));
+    assert!(evaluate("input.height > 720", &hd).unwrap());
+    assert!(!evaluate("input.height > 720", &sd).unwrap());
+    assert!(evaluate("input.height <= 720", &sd).unwrap());
+    assert!(evaluate("input.fps < 30 && input.fps > 29.9", &sd).unwrap());
+}
+
+#[test]
+fn compares_text() {
+    let aac = variables(1080, Some("aac"));
+    let opus = variables(1080, Some("opus"));
+    assert!(!evaluate("input.audio_codec != 'aac'", &aac).unwrap());
+    assert!(evaluate("input.audio_codec != \"aac\"", &opus).unwrap());
+    assert!(evaluate("var.mode == 'fast'", &aac).unwrap());
+}
+
+#[test]
+fn missing_values_are_null() {
+    let silent = variables(1080, None);
+    assert!(evaluate("input.audio_codec != 'aac'", &silent).unwrap());
+    assert!(!evaluate("input.audio_bitrate > 0", &silent).unwrap());
+    assert!(evaluate("!input.has_audio", &silent).unwrap());
+    assert!(evaluate("var.undefined == null", &silent).unwrap());
+}
+
+#[test]
+fn combines_conditions() {
+    let hd = variables(1080, Some("aac"));
+    assert!(evaluate(
+        "!(input.height <= 720 || var.mode != 'fast') && input.has_video",
+        &hd
+    )
+    .unwrap());
+    assert!(evaluate("input.width == 1920 || false && false", &hd).unwrap());
+}
+
+#[test]
+fn variables_given_as_text_compare_as_numbers() {
+    let vars = BTreeMap::from([("crf".to_string(), "28".to_string())]);
+    let variables = Variables::new().with_vars(&vars);
+    assert!(evaluate("var.crf > 23", &variables).unwrap());
+    assert!(evaluate("var.crf == 28.0", &variables).unwrap());
+}
+
+#[test]
+fn ordering_text_is_an_error() {
+    let hd = variables(1080, Some("aac"));
+    assert!(evaluate("input.video_codec > 1", &hd).is_err());
+}
+
+#[test]
+fn malformed_conditions_are_rejected() {
+    for condition in [
+        "input.height >",
+        "input.height = 720",
+        "input.height > 720 & input.has_audio",
+        "(input.has_audio",
+        "input.audio_codec == 'aac",
+        "input.height 720",
+    ] {
+        assert!(check(condition).is_err(), "{} was accepted", condition);
+    }
+}
+
+#[test]
+fn unknown_variables_are_rejected() {
+    assert!(check("input.heigth > 720").is_err());
+    assert!(check("height > 720").is_err());
+    assert!(check("input.height > 720 && var.anything == 'x'").is_ok());
+}
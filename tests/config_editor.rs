@@ -0,0 +1,177 @@
+//! Edits config files in place, keeping what the user wrote around the edited field.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kffmpeg::config_editor::{append_command, update_command_encoders, update_command_options};
+use kffmpeg::{Command, CommandOption, Config, Encoder};
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"# My recipes
+ffmpeg_path: ffmpeg
+commands: # most used first
+  - title: Encode
+    # Quality first
+    options:
+      - flag: -crf # lower is better
+        value: "23"
+      - flag: -preset
+        value: slow
+    output_extension: .mp4 # keep mp4
+    output_filename_suffix: _encoded
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+
+  # Only for the phone
+  - title: Small
+    options: [{flag: -b:v, value: 1M}]
+    encoders:
+      - name: h264_nvenc
+      - name: libx264
+    output_extension: .mp4
+    output_filename_suffix: _small
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+
+# Ideas for later
+"#;
+
+fn write_config(text: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, text).unwrap();
+    (dir, path)
+}
+
+fn load(path: &Path) -> Config {
+    Config::load(path).unwrap()
+}
+
+fn option(flag: &str, value: &str) -> CommandOption {
+    CommandOption {
+        flag: flag.to_string(),
+        value: value.to_string(),
+        when: None,
+    }
+}
+
+/// The lines of `text` that are not items of a list under a command, or inside them.
+fn outside_lists(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter(|line| !line.starts_with("    -") && !line.starts_with("      "))
+        .collect()
+}
+
+/// The lines of `text` that are comments or blank, in order.
+fn comments(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        .collect()
+}
+
+#[test]
+fn updates_the_options_in_place() {
+    let (_dir, path) = write_config(CONFIG);
+    let options = [option("-crf", "28"), option("-tune", "film")];
+    update_command_options(&path, "Encode", &options).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let config = load(&path);
+    assert_eq!(config.commands[0].options, options);
+    assert_eq!(config.commands[1].options, [option("-b:v", "1M")]);
+    // Only the items of the lists changed.
+    assert_eq!(outside_lists(&text), outside_lists(CONFIG));
+    assert!(text.contains("    output_extension: .mp4 # keep mp4\n"));
+    assert!(text.ends_with("# Ideas for later\n"));
+}
+
+#[test]
+fn rewrites_the_command_when_the_options_are_inline() {
+    let (_dir, path) = write_config(CONFIG);
+    update_command_options(&path, "Small", &[option("-b:v", "2M")]).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let config = load(&path);
+    assert_eq!(config.commands[1].options, [option("-b:v", "2M")]);
+    assert_eq!(config.commands[1].encoders.len(), 2);
+    assert_eq!(config.commands[0], load_original().commands[0]);
+    assert_eq!(comments(&text), comments(CONFIG));
+}
+
+#[test]
+fn updates_the_encoders_in_place() {
+    let (_dir, path) = write_config(CONFIG);
+    let encoders = [Encoder {
+        name: "libx265".to_string(),
+        options: vec![option("-crf", "26")],
+    }];
+    update_command_encoders(&path, "Small", &encoders).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let config = load(&path);
+    assert_eq!(config.commands[1].encoders, encoders);
+    assert!(text.contains("    options: [{flag: -b:v, value: 1M}]\n"));
+    assert_eq!(comments(&text), comments(CONFIG));
+}
+
+#[test]
+fn adds_encoders_to_a_command_without_them() {
+    let (_dir, path) = write_config(CONFIG);
+    let encoders = [Encoder {
+        name: "libx264".to_string(),
+        options: Vec::new(),
+    }];
+    update_command_encoders(&path, "Encode", &encoders).unwrap();
+
+    let config = load(&path);
+    assert_eq!(config.commands[0].encoders, encoders);
+    assert_eq!(
+        config.commands[0].options,
+        load_original().commands[0].options
+    );
+    assert_eq!(config.commands[1], load_original().commands[1]);
+}
+
+#[test]
+fn fails_for_an_unknown_title() {
+    let (_dir, path) = write_config(CONFIG);
+    let result = update_command_options(&path, "Missing", &[]);
+    assert_eq!(
+        result,
+        Err("Missing was not found in config file.".to_string())
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG);
+}
+
+#[test]
+fn appends_a_command_after_the_last_one() {
+    let (_dir, path) = write_config(CONFIG);
+    let mut command: Command = load_original().commands[0].clone();
+    command.title = "Encode copy".to_string();
+    append_command(&path, &command).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let config = load(&path);
+    let titles: Vec<&str> = config.commands.iter().map(|c| c.title.as_str()).collect();
+    assert_eq!(titles, ["Encode", "Small", "Encode copy"]);
+    assert_eq!(config.commands[2], command);
+    assert!(text.starts_with(CONFIG[..CONFIG.find("# Ideas").unwrap()].trim_end()));
+    assert!(text.ends_with("# Ideas for later\n"));
+}
+
+#[test]
+fn appends_the_first_command() {
+    let (_dir, path) = write_config("ffmpeg_path: ffmpeg\ncommands:\n");
+    append_command(&path, &load_original().commands[0]).unwrap();
+    assert_eq!(load(&path).commands, [load_original().commands[0].clone()]);
+}
+
+#[test]
+fn keeps_a_missing_trailing_newline() {
+    let (_dir, path) = write_config(CONFIG.trim_end());
+    update_command_options(&path, "Encode", &[option("-crf", "20")]).unwrap();
+    assert!(!fs::read_to_string(&path).unwrap().ends_with('\n'));
+}
+
+fn load_original() -> Config {
+    let (_dir, path) = write_config(CONFIG);
+    load(&path)
+}
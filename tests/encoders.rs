@@ -0,0 +1,55 @@
+//! Parsing `ffmpeg -encoders` and choosing an encoder from the candidates of a command.
+
+use std::collections::HashSet;
+
+use kffmpeg::encoders::{choose, is_codec_flag, parse_encoders};
+use kffmpeg::Encoder;
+
+const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D h264_qsv             H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (Intel Quick Sync Video acceleration) (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+";
+
+fn encoder(name: &str) -> Encoder {
+    Encoder {
+        name: name.to_string(),
+        options: Vec::new(),
+    }
+}
+
+#[test]
+fn lists_encoders_after_the_legend() {
+    assert_eq!(
+        parse_encoders(ENCODERS),
+        HashSet::from(["libx264", "h264_qsv", "aac"].map(String::from))
+    );
+}
+
+#[test]
+fn chooses_the_first_available_candidate() {
+    let available = parse_encoders(ENCODERS);
+    let candidates = ["h264_nvenc", "h264_qsv", "libx264"].map(encoder);
+    assert_eq!(choose(&candidates, &available).unwrap().name, "h264_qsv");
+    assert!(choose(&[encoder("h264_nvenc")], &available).is_none());
+}
+
+#[test]
+fn codec_flags_are_matched_by_stream() {
+    for flag in ["-c:v", "-vcodec", "-codec:v", "-c:v:0", "-c"] {
+        assert!(is_codec_flag(flag, Some('v')), "{}", flag);
+    }
+    for flag in ["-c:a", "-acodec", "-b:v", "-crf"] {
+        assert!(!is_codec_flag(flag, Some('v')), "{}", flag);
+    }
+    assert!(is_codec_flag("-c:a", None));
+}
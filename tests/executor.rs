@@ -0,0 +1,69 @@
+//! Runs expanded commands through the recording executor.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use kffmpeg::condition::Variables;
+use kffmpeg::error::Error;
+use kffmpeg::execute::{run_steps, DryRunExecutor, RecordingExecutor};
+use kffmpeg::template::{expand_steps, ExpandedStep, StepValues};
+use kffmpeg::Config;
+
+const CONFIG: &str = r#"
+ffmpeg_path: ffmpeg
+commands:
+  - title: Two pass
+    two_pass: true
+    options:
+      - flag: -b:v
+        value: 2M
+    output_extension: .mp4
+    output_filename_suffix: _2pass
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+"#;
+
+fn two_pass_steps() -> Vec<ExpandedStep> {
+    let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+    let command = &config.commands[0];
+    let options: Vec<String> = command
+        .options
+        .iter()
+        .flat_map(|option| [option.flag.clone(), option.value.clone()])
+        .collect();
+    expand_steps(
+        command,
+        &StepValues {
+            ffmpeg_path: "ffmpeg",
+            input_path: Path::new("in.mp4"),
+            inputs: &HashMap::new(),
+            options: &options,
+            output_path: Path::new("out.mp4"),
+            temp_dir: Path::new("tmp"),
+        },
+        &Variables::new(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn runs_every_step_in_order() {
+    let steps = two_pass_steps();
+    let executor = RecordingExecutor::new();
+    run_steps(&executor, &steps, None, &mut ()).unwrap();
+    assert_eq!(executor.steps(), steps);
+}
+
+#[test]
+fn stops_at_the_first_failed_step() {
+    let steps = two_pass_steps();
+    let executor = RecordingExecutor::with_exit_codes([1]);
+    let result = run_steps(&executor, &steps, None, &mut ());
+    assert!(matches!(result, Err(Error::FfmpegFailed(Some(1)))));
+    assert_eq!(executor.steps(), steps[..1]);
+}
+
+#[test]
+fn dry_run_succeeds_without_running() {
+    let steps = two_pass_steps();
+    assert!(run_steps(&DryRunExecutor, &steps, None, &mut ()).is_ok());
+}
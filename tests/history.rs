@@ -0,0 +1,52 @@
+//! Searches the history.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use kffmpeg::history::HistoryEntry;
+
+fn entry() -> HistoryEntry {
+    HistoryEntry {
+        timestamp: "2024-05-01T12:00:00+09:00".to_string(),
+        hash: "3966fc31".to_string(),
+        title: "Encode to H.265".to_string(),
+        input_paths: vec![PathBuf::from("/videos/Holiday.mp4")],
+        inputs: BTreeMap::from([("audio".to_string(), PathBuf::from("/music/BGM.wav"))]),
+        options: Vec::new(),
+        command_options: None,
+        vars: BTreeMap::new(),
+        output_path: PathBuf::from("/out/Holiday_h265.mp4"),
+        argv: Vec::new(),
+        succeeded: true,
+        exit_code: Some(0),
+        duration: 1.0,
+        output_size: None,
+        log_path: Some(PathBuf::from("/logs/holiday.log")),
+        quality: None,
+    }
+}
+
+#[test]
+fn matches_the_title_and_the_hash() {
+    assert!(entry().matches("h.265"));
+    assert!(entry().matches("3966"));
+}
+
+#[test]
+fn matches_every_path() {
+    assert!(entry().matches("videos/holiday"));
+    assert!(entry().matches("bgm.wav"));
+    assert!(entry().matches("_H265"));
+}
+
+#[test]
+fn does_not_match_other_fields() {
+    assert!(!entry().matches("2024-05"));
+    assert!(!entry().matches("logs"));
+    assert!(!entry().matches("vp9"));
+}
+
+#[test]
+fn matches_everything_with_an_empty_query() {
+    assert!(entry().matches(""));
+}
@@ -0,0 +1,115 @@
+//! Plans and runs jobs through the library, as the CLI does, without running ffmpeg.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use kffmpeg::error::Error;
+use kffmpeg::execute::{DryRunExecutor, Executor, RecordingExecutor};
+use kffmpeg::job::{self, Context, JobRequest, Observer};
+use kffmpeg::template::ExpandedStep;
+use kffmpeg::Config;
+
+const CONFIG: &str = r#"
+ffmpeg_path: ffmpeg
+commands:
+  - title: Two pass
+    two_pass: true
+    options:
+      - flag: -b:v
+        value: 2M
+    output_extension: .mp4
+    output_filename_suffix: _2pass
+    command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+"#;
+
+fn dry_run_context(executor: Box<dyn Executor>) -> Context {
+    Context::new("ffmpeg", "ffprobe", executor, Path::new("logs")).with_dry_run(true)
+}
+
+fn request(config: &Config) -> JobRequest<'_> {
+    let command = &config.commands[0];
+    JobRequest {
+        command,
+        input_paths: vec![PathBuf::from("in.mp4")],
+        inputs: HashMap::new(),
+        options: command.options.clone(),
+        vars: BTreeMap::new(),
+        output_path: PathBuf::from("out.mp4"),
+        target_size: None,
+    }
+}
+
+/// Keeps what the job reported.
+#[derive(Default)]
+struct Recorder {
+    started: Vec<Vec<ExpandedStep>>,
+    finished: Vec<bool>,
+}
+
+impl kffmpeg::execute::Observer for Recorder {}
+
+impl Observer for Recorder {
+    fn steps_started(&mut self, steps: &[ExpandedStep]) {
+        self.started.push(steps.to_vec());
+    }
+
+    fn steps_finished(&mut self, result: &Result<(), Error>) {
+        self.finished.push(result.is_ok());
+    }
+}
+
+#[test]
+fn plans_the_steps_of_the_command() {
+    let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+    let context = dry_run_context(Box::new(DryRunExecutor));
+    let plan = job::plan(&context, request(&config), &mut ()).unwrap();
+    let argv: Vec<&[String]> = plan.steps().iter().map(|step| &step.argv[..]).collect();
+    assert_eq!(argv.len(), 2);
+    assert!(argv[0].contains(&"-pass".to_string()));
+    assert_eq!(argv[1].last().unwrap(), "out.mp4");
+}
+
+#[test]
+fn runs_the_planned_steps_and_reports_them() {
+    let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+    let context = dry_run_context(Box::new(RecordingExecutor::new()));
+    let plan = job::plan(&context, request(&config), &mut ()).unwrap();
+    let steps = plan.steps().to_vec();
+    let mut recorder = Recorder::default();
+    let result = job::run(&context, plan, &mut recorder);
+    assert!(result.result.is_ok());
+    assert_eq!(result.log_path, None);
+    assert_eq!(recorder.started, vec![steps]);
+    assert_eq!(recorder.finished, vec![true]);
+}
+
+#[test]
+fn fails_with_the_exit_code_of_the_failed_step() {
+    let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+    let context = dry_run_context(Box::new(RecordingExecutor::with_exit_codes([0, 1])));
+    let plan = job::plan(&context, request(&config), &mut ()).unwrap();
+    let mut recorder = Recorder::default();
+    let result = job::run(&context, plan, &mut recorder);
+    assert!(matches!(result.result, Err(Error::FfmpegFailed(Some(1)))));
+    assert_eq!(recorder.finished, vec![false]);
+}
+
+#[test]
+fn concat_needs_two_files() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+ffmpeg_path: ffmpeg
+commands:
+  - title: Join
+    concat: true
+    options: []
+    output_extension: .mp4
+    output_filename_suffix: _concat
+    command: ["{{ffmpeg_path}}", -f, concat, -safe, "0", -i, "{{input_path}}", "{{output_path}}"]
+"#,
+    )
+    .unwrap();
+    let context = dry_run_context(Box::new(DryRunExecutor));
+    let result = job::plan(&context, request(&config), &mut ());
+    assert!(matches!(result, Err(Error::Validation(_))));
+}
@@ -0,0 +1,90 @@
+//! Reads the end of a job log.
+
+use std::fs;
+use std::path::Path;
+
+use kffmpeg::job_log::JobLog;
+
+fn log_with(dir: &Path, text: &str) -> JobLog {
+    let log = JobLog::create(dir, "3966fc31", Path::new("/videos/clip.mp4")).unwrap();
+    fs::write(log.path(), text).unwrap();
+    log
+}
+
+#[test]
+fn names_the_log_after_the_hash_and_the_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = JobLog::create(dir.path(), "3966fc31", Path::new("clip.mp4")).unwrap();
+    let second = JobLog::create(dir.path(), "3966fc31", Path::new("clip.mp4")).unwrap();
+    let name = first.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.ends_with("_3966fc31_clip.log"), "{}", name);
+    assert_ne!(first.path(), second.path());
+}
+
+#[test]
+fn returns_the_last_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "one\ntwo\nthree\nfour\n");
+    assert_eq!(log.tail(2), ["three", "four"]);
+}
+
+#[test]
+fn returns_every_line_of_a_short_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "one\ntwo");
+    assert_eq!(log.tail(20), ["one", "two"]);
+}
+
+#[test]
+fn returns_nothing_for_zero_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "one\ntwo\n");
+    assert!(log.tail(0).is_empty());
+}
+
+#[test]
+fn returns_nothing_for_an_empty_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "");
+    assert!(log.tail(5).is_empty());
+}
+
+#[test]
+fn counts_each_progress_update_as_a_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(
+        dir.path(),
+        "Input #0\r\nframe=1 time=00:00:01.00\rframe=2 time=00:00:02.00\rerror: broken\n",
+    );
+    assert_eq!(
+        log.tail(3),
+        [
+            "frame=1 time=00:00:01.00",
+            "frame=2 time=00:00:02.00",
+            "error: broken"
+        ]
+    );
+}
+
+#[test]
+fn skips_blank_lines_and_trailing_spaces() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "one  \n\n   \ntwo\t\n\n");
+    assert_eq!(log.tail(2), ["one", "two"]);
+}
+
+#[test]
+fn keeps_invalid_utf8() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = JobLog::create(dir.path(), "3966fc31", Path::new("clip.mp4")).unwrap();
+    fs::write(log.path(), b"ok\n\xffbad\n").unwrap();
+    assert_eq!(log.tail(2), ["ok", "\u{fffd}bad"]);
+}
+
+#[test]
+fn returns_nothing_when_the_log_is_gone() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = log_with(dir.path(), "one\n");
+    fs::remove_file(log.path()).unwrap();
+    assert!(log.tail(5).is_empty());
+}
@@ -0,0 +1,132 @@
+//! Parses paths as they are typed or dropped into a terminal.
+
+use std::fs;
+use std::path::PathBuf;
+
+use kffmpeg::paths::{normalize_path, parse_path, parse_paths, split_paths};
+
+#[test]
+fn splits_on_whitespace() {
+    assert_eq!(
+        split_paths("  a.mp4\tb.mp4  c.mp4 "),
+        ["a.mp4", "b.mp4", "c.mp4"]
+    );
+}
+
+#[test]
+fn keeps_quoted_spaces() {
+    assert_eq!(
+        split_paths(r#"'my movie.mp4' "other movie.mp4""#),
+        ["my movie.mp4", "other movie.mp4"]
+    );
+}
+
+#[test]
+fn joins_quoted_parts_of_a_word() {
+    assert_eq!(split_paths(r#"dir/'my movie'.mp4"#), ["dir/my movie.mp4"]);
+}
+
+#[test]
+fn keeps_an_empty_quoted_word() {
+    assert_eq!(split_paths("'' a.mp4"), ["", "a.mp4"]);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn unescapes_backslashes() {
+    assert_eq!(
+        split_paths(r"my\ movie.mp4 it\'s.mp4"),
+        ["my movie.mp4", "it's.mp4"]
+    );
+}
+
+#[cfg(not(windows))]
+#[test]
+fn unescapes_only_quotes_and_backslashes_in_double_quotes() {
+    assert_eq!(
+        split_paths(r#""say \"hi\" \\ \n.mp4""#),
+        [r#"say "hi" \ \n.mp4"#]
+    );
+}
+
+#[test]
+fn keeps_backslashes_in_single_quotes() {
+    assert_eq!(split_paths(r"'a\ b.mp4'"), [r"a\ b.mp4"]);
+}
+
+#[test]
+fn converts_a_file_uri() {
+    assert_eq!(
+        normalize_path("file:///home/me/My%20Movie.mp4"),
+        PathBuf::from("/home/me/My Movie.mp4")
+    );
+}
+
+#[test]
+fn skips_the_host_of_a_file_uri() {
+    assert_eq!(
+        normalize_path("file://localhost/tmp/a.mp4"),
+        PathBuf::from("/tmp/a.mp4")
+    );
+}
+
+#[test]
+fn decodes_multibyte_characters_of_a_file_uri() {
+    assert_eq!(
+        normalize_path("file:///tmp/%E5%8B%95%E7%94%BB.mp4"),
+        PathBuf::from("/tmp/動画.mp4")
+    );
+}
+
+#[test]
+fn keeps_invalid_percent_escapes() {
+    assert_eq!(
+        normalize_path("file:///tmp/100%zz%2"),
+        PathBuf::from("/tmp/100%zz%2")
+    );
+}
+
+#[test]
+fn expands_the_home_directory() {
+    let home = dirs::home_dir().unwrap();
+    assert_eq!(normalize_path("~"), home);
+    assert_eq!(normalize_path("~/a.mp4"), home.join("a.mp4"));
+    assert_eq!(normalize_path("a~/b.mp4"), PathBuf::from("a~/b.mp4"));
+}
+
+#[test]
+fn parses_an_empty_line_into_no_paths() {
+    assert!(parse_paths("   ").is_empty());
+}
+
+#[test]
+fn parses_several_dropped_paths() {
+    assert_eq!(
+        parse_paths("'/tmp/my movie.mp4' file:///tmp/b%20c.mp4 /tmp/d.mp4\n"),
+        [
+            PathBuf::from("/tmp/my movie.mp4"),
+            PathBuf::from("/tmp/b c.mp4"),
+            PathBuf::from("/tmp/d.mp4"),
+        ]
+    );
+}
+
+#[test]
+fn takes_an_existing_path_with_spaces_as_a_whole() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("my movie.mp4");
+    fs::write(&path, "").unwrap();
+    assert_eq!(parse_paths(path.to_str().unwrap()), [path]);
+}
+
+#[test]
+fn parses_a_single_path_that_does_not_exist_yet() {
+    assert_eq!(
+        parse_path(" '/tmp/new dir/out.mp4' "),
+        PathBuf::from("/tmp/new dir/out.mp4")
+    );
+    assert_eq!(
+        parse_path("/tmp/new dir/out.mp4"),
+        PathBuf::from("/tmp/new dir/out.mp4")
+    );
+}
@@ -0,0 +1,67 @@
+//! Parsing the scores of the psnr, ssim and libvmaf filters, and the filter graph that computes them.
+
+use std::path::Path;
+
+use kffmpeg::quality::{compare_args, parse_filters, parse_scores, Scores};
+
+const STDERR: &str = "\
+Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'out.mp4':
+[Parsed_psnr_4 @ 0x5581c2a0] PSNR y:34.957 u:41.218 v:41.517 average:36.383 min:33.716 max:39.829
+[Parsed_ssim_5 @ 0x5581c2c0] SSIM Y:0.957163 (13.68) U:0.978210 (16.62) V:0.979032 (16.78) All:0.965003 (14.56)
+[Parsed_libvmaf_6 @ 0x5581c2e0] VMAF score: 93.452141
+";
+
+#[test]
+fn parses_every_score() {
+    assert_eq!(
+        parse_scores(STDERR),
+        Scores {
+            psnr: Some(36.383),
+            ssim: Some(0.965003),
+            vmaf: Some(93.452141),
+        }
+    );
+}
+
+#[test]
+fn missing_metrics_are_none() {
+    let scores =
+        parse_scores("[Parsed_psnr_0 @ 0x1] PSNR y:inf u:inf v:inf average:inf min:inf max:inf\n");
+    assert_eq!(scores.psnr, Some(f64::INFINITY));
+    assert_eq!(scores.ssim, None);
+    assert_eq!(scores.vmaf, None);
+    assert_eq!(scores.summary(), "PSNR inf dB");
+}
+
+#[test]
+fn lists_filters() {
+    let filters = parse_filters(
+        "Filters:
+  T.. = Timeline support
+  | = Source or sink filter
+ TS. psnr              VV->V      Calculate the PSNR between two video streams.
+ ... libvmaf           VV->V      Calculate the VMAF between two video streams.
+ ... nullsink          V->|       Do absolutely nothing with the input video.
+",
+    );
+    assert!(filters.contains("psnr"));
+    assert!(filters.contains("libvmaf"));
+    assert!(filters.contains("nullsink"));
+    assert!(!filters.contains("Timeline"));
+}
+
+#[test]
+fn scales_the_distorted_video_to_the_reference() {
+    let args = compare_args(
+        Path::new("in.mp4"),
+        Path::new("out.mp4"),
+        1920,
+        1080,
+        &["psnr", "ssim"],
+    );
+    let position = |arg: &str| args.iter().position(|a| a == arg).unwrap();
+    assert!(position("out.mp4") < position("in.mp4"));
+    let graph = &args[position("-lavfi") + 1];
+    assert!(graph.starts_with("[0:v]scale=1920:1080:"));
+    assert!(graph.ends_with("[d0][r0]psnr;[d1][r1]ssim"));
+}
@@ -0,0 +1,125 @@
+//! Rendering the summary of the jobs as CSV, JSON and Markdown.
+
+use std::path::{Path, PathBuf};
+
+use kffmpeg::report::{render, JobReport, ReportFormat, Totals};
+
+fn job(name: &str, input_size: u64, output_size: u64, error: Option<&str>) -> JobReport {
+    JobReport {
+        input_paths: vec![PathBuf::from(format!("/videos/{}.mp4", name))],
+        output_path: PathBuf::from(format!("/videos/{}_light.mp4", name)),
+        hash: "3966fc31".to_string(),
+        title: "Make video lighter with H.264".to_string(),
+        duration: Some(60.0),
+        wall_time: 12.5,
+        input_size: Some(input_size),
+        output_size: Some(output_size),
+        compression_ratio: error
+            .is_none()
+            .then_some(input_size as f64 / output_size as f64),
+        psnr: None,
+        ssim: None,
+        vmaf: None,
+        succeeded: error.is_none(),
+        error: error.map(str::to_string),
+    }
+}
+
+fn jobs() -> Vec<JobReport> {
+    vec![
+        job("a", 40_000_000, 10_000_000, None),
+        job("b, c", 20_000_000, 10_000_000, None),
+        job(
+            "d",
+            30_000_000,
+            1_000,
+            Some("ffmpeg failed with exit code 1."),
+        ),
+    ]
+}
+
+#[test]
+fn totals_count_the_sizes_of_succeeded_jobs() {
+    let totals = Totals::of(&jobs());
+    assert_eq!((totals.jobs, totals.succeeded, totals.failed), (3, 2, 1));
+    assert_eq!(totals.input_size, 60_000_000);
+    assert_eq!(totals.saved_size, 40_000_000);
+    assert_eq!(
+        totals.summary(),
+        "3 jobs, 2 succeeded, 1 failed in 37.5s. 60.00 MB -> 20.00 MB, 40.00 MB (67%) saved."
+    );
+}
+
+#[test]
+fn csv_has_a_row_per_job_and_quotes_commas() {
+    let csv = render(&jobs(), ReportFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("input,output,hash,"));
+    assert_eq!(
+        lines[1],
+        "/videos/a.mp4,/videos/a_light.mp4,3966fc31,Make video lighter with H.264,60.000,12.500,40000000,10000000,4.000,succeeded,"
+    );
+    assert!(lines[2].starts_with("\"/videos/b, c.mp4\",\"/videos/b, c_light.mp4\","));
+    assert!(lines[3].ends_with(",,failed,ffmpeg failed with exit code 1."));
+}
+
+#[test]
+fn json_has_the_jobs_and_the_totals() {
+    let json: serde_json::Value =
+        serde_json::from_str(&render(&jobs(), ReportFormat::Json)).unwrap();
+    assert_eq!(json["jobs"].as_array().unwrap().len(), 3);
+    assert_eq!(json["jobs"][0]["compression_ratio"], 4.0);
+    assert_eq!(json["total"]["failed"], 1);
+}
+
+#[test]
+fn markdown_is_a_table_with_the_totals() {
+    let markdown = render(&jobs(), ReportFormat::Markdown);
+    let lines: Vec<&str> = markdown.lines().collect();
+    assert!(lines[0].starts_with("| Input | Output |"));
+    assert_eq!(
+        lines[2],
+        "| a.mp4 | a_light.mp4 | Make video lighter with H.264 (3966fc31) | 60.0s | 12.5s | 40.00 MB | 10.00 MB | 4.00x | succeeded |"
+    );
+    assert!(lines[4].ends_with("| failed: ffmpeg failed with exit code 1. |"));
+    assert!(lines
+        .last()
+        .unwrap()
+        .starts_with("3 jobs, 2 succeeded, 1 failed"));
+}
+
+#[test]
+fn quality_columns_are_added_for_the_measured_metrics() {
+    let mut jobs = jobs();
+    jobs[0].psnr = Some(38.214);
+    jobs[0].ssim = Some(0.98123);
+    let csv = render(&jobs, ReportFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(lines[0].ends_with(",compression_ratio,psnr,ssim,status,error"));
+    assert!(lines[1].ends_with(",4.000,38.21,0.9812,succeeded,"));
+    assert!(lines[2].ends_with(",2.000,,,succeeded,"));
+
+    let markdown = render(&jobs, ReportFormat::Markdown);
+    let lines: Vec<&str> = markdown.lines().collect();
+    assert!(lines[0].ends_with("| Ratio | PSNR | SSIM | Status |"));
+    assert!(lines[2].ends_with("| 4.00x | 38.21 | 0.9812 | succeeded |"));
+
+    let json: serde_json::Value = serde_json::from_str(&render(&jobs, ReportFormat::Json)).unwrap();
+    assert_eq!(json["jobs"][0]["ssim"], 0.98123);
+    assert!(json["jobs"][0].get("vmaf").is_none());
+    assert!(json["jobs"][1].get("psnr").is_none());
+}
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(
+        ReportFormat::from_path(Path::new("report.CSV")),
+        Some(ReportFormat::Csv)
+    );
+    assert_eq!(
+        ReportFormat::from_path(Path::new("report.md")),
+        Some(ReportFormat::Markdown)
+    );
+    assert_eq!(ReportFormat::from_path(Path::new("report.txt")), None);
+}
@@ -0,0 +1,47 @@
+//! Parses and formats sizes.
+
+use kffmpeg::size::{format_size, parse_size};
+
+#[test]
+fn parses_decimal_suffixes() {
+    assert_eq!(parse_size("700K"), Some(700_000));
+    assert_eq!(parse_size("25M"), Some(25_000_000));
+    assert_eq!(parse_size("2G"), Some(2_000_000_000));
+}
+
+#[test]
+fn parses_binary_suffixes() {
+    assert_eq!(parse_size("1Ki"), Some(1024));
+    assert_eq!(parse_size("8Mi"), Some(8 * 1024 * 1024));
+    assert_eq!(parse_size("1Gi"), Some(1024 * 1024 * 1024));
+}
+
+#[test]
+fn ignores_case_a_trailing_b_and_spaces() {
+    assert_eq!(parse_size("25mb"), Some(25_000_000));
+    assert_eq!(parse_size("8MiB"), Some(8 * 1024 * 1024));
+    assert_eq!(parse_size(" 25 M "), Some(25_000_000));
+    assert_eq!(parse_size("512B"), Some(512));
+}
+
+#[test]
+fn parses_fractions_and_plain_bytes() {
+    assert_eq!(parse_size("1.5G"), Some(1_500_000_000));
+    assert_eq!(parse_size("0.5Ki"), Some(512));
+    assert_eq!(parse_size("1234"), Some(1234));
+}
+
+#[test]
+fn rejects_invalid_sizes() {
+    for size in ["", "M", "abc", "25X", "-1M", "0", "0M", "1 2M"] {
+        assert_eq!(parse_size(size), None, "{}", size);
+    }
+}
+
+#[test]
+fn formats_with_a_decimal_unit() {
+    assert_eq!(format_size(999), "999 B");
+    assert_eq!(format_size(1_500), "1.5 KB");
+    assert_eq!(format_size(1_500_000), "1.50 MB");
+    assert_eq!(format_size(2_000_000_000), "2.00 GB");
+}
@@ -0,0 +1,168 @@
+//! Expansion of command templates, including file names that look like placeholders.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use kffmpeg::condition::Variables;
+use kffmpeg::template::{escape, expand_steps, expand_template, parse, Segment, StepValues};
+use kffmpeg::Command;
+
+fn template(elements: &[&str]) -> Vec<String> {
+    elements.iter().map(|s| s.to_string()).collect()
+}
+
+fn options() -> Vec<String> {
+    template(&["-c:v", "libx264", "-crf", "23"])
+}
+
+fn expand(elements: &[&str], input_path: &str, output_path: &str) -> Result<Vec<String>, String> {
+    expand_template(
+        &template(elements),
+        "ffmpeg",
+        Path::new(input_path),
+        &HashMap::new(),
+        &options(),
+        Path::new(output_path),
+        &HashMap::new(),
+    )
+}
+
+const COMMAND: [&str; 5] = [
+    "{{ffmpeg_path}}",
+    "-i",
+    "{{input_path}}",
+    "{{options}}",
+    "{{output_path}}",
+];
+
+#[test]
+fn expands_a_plain_command() {
+    assert_eq!(
+        expand(&COMMAND, "in.mp4", "out.mp4").unwrap(),
+        template(&["ffmpeg", "-i", "in.mp4", "-c:v", "libx264", "-crf", "23", "out.mp4"])
+    );
+}
+
+#[test]
+fn input_named_like_a_placeholder_is_kept() {
+    for name in [
+        "{{output_path}}.mp4",
+        "{{options}}.mp4",
+        "{{ffmpeg_path}}.mp4",
+        "{{inputs.logo}}.mp4",
+        "{{steps.audio.output}}.mp4",
+        "{{{{.mp4",
+        "{{.mp4",
+    ] {
+        let argv = expand(&COMMAND, name, "out.mp4").unwrap();
+        assert_eq!(argv[2], name);
+        assert_eq!(argv.len(), 8);
+    }
+}
+
+#[test]
+fn output_named_like_a_placeholder_is_kept() {
+    let argv = expand(&COMMAND, "in.mp4", "{{input_path}} {{options}}.mp4").unwrap();
+    assert_eq!(argv.last().unwrap(), "{{input_path}} {{options}}.mp4");
+}
+
+#[test]
+fn named_input_and_step_output_are_not_expanded_again() {
+    let inputs = HashMap::from([("logo".to_string(), PathBuf::from("{{output_path}}.png"))]);
+    let step_outputs = HashMap::from([("audio".to_string(), PathBuf::from("{{options}}.wav"))]);
+    let argv = expand_template(
+        &template(&["{{inputs.logo}}", "{{steps.audio.output}}"]),
+        "ffmpeg",
+        Path::new("in.mp4"),
+        &inputs,
+        &options(),
+        Path::new("out.mp4"),
+        &step_outputs,
+    )
+    .unwrap();
+    assert_eq!(argv, template(&["{{output_path}}.png", "{{options}}.wav"]));
+}
+
+#[test]
+fn options_inside_an_element_are_joined() {
+    let argv = expand(&["-vf", "scale=1280:-2,{{options}}"], "in.mp4", "out.mp4").unwrap();
+    assert_eq!(argv[1], "scale=1280:-2,-c:v libx264 -crf 23");
+}
+
+#[test]
+fn placeholders_inside_an_element_are_expanded() {
+    let argv = expand(&["movie={{input_path}}[in]"], "a b.mp4", "out.mp4").unwrap();
+    assert_eq!(argv, template(&["movie=a b.mp4[in]"]));
+}
+
+#[test]
+fn four_braces_are_a_literal() {
+    let argv = expand(&["drawtext=text='{{{{title}}'"], "in.mp4", "out.mp4").unwrap();
+    assert_eq!(argv, template(&["drawtext=text='{{title}}'"]));
+}
+
+#[test]
+fn escaped_text_comes_back_unchanged() {
+    for text in ["{{output_path}}", "a{{{{b", "{{", "}}"] {
+        assert_eq!(
+            parse(&escape(text)).unwrap(),
+            vec![Segment::Literal(text.to_string())]
+        );
+    }
+}
+
+#[test]
+fn unclosed_braces_are_an_error() {
+    assert!(expand(&["{{input_path"], "in.mp4", "out.mp4").is_err());
+}
+
+#[test]
+fn unknown_placeholder_is_an_error() {
+    assert!(expand(&["{{input}}"], "in.mp4", "out.mp4").is_err());
+}
+
+#[test]
+fn undefined_input_is_an_error() {
+    assert!(expand(&["{{inputs.logo}}"], "in.mp4", "out.mp4").is_err());
+}
+
+#[test]
+fn x265_pass_settings_are_added_to_the_existing_params() {
+    let command: Command = serde_yaml::from_str(
+        r#"
+title: Two pass x265
+two_pass: true
+options: []
+output_extension: .mp4
+output_filename_suffix: _2pass
+command: ["{{ffmpeg_path}}", -i, "{{input_path}}", "{{options}}", "{{output_path}}"]
+"#,
+    )
+    .unwrap();
+    let steps = expand_steps(
+        &command,
+        &StepValues {
+            ffmpeg_path: "ffmpeg",
+            input_path: Path::new("in.mp4"),
+            inputs: &HashMap::new(),
+            options: &template(&["-c:v", "libx265", "-x265-params", "crf=20"]),
+            output_path: Path::new("out.mp4"),
+            temp_dir: Path::new("C:tmp"),
+        },
+        &Variables::new(),
+    )
+    .unwrap();
+    let passlog = Path::new("C:tmp").join("passlog").display().to_string();
+    for (pass, step) in [1, 2].iter().zip(steps.iter()) {
+        let params: Vec<&String> = step
+            .argv
+            .iter()
+            .skip_while(|s| *s != "-x265-params")
+            .collect();
+        assert_eq!(step.argv.iter().filter(|s| *s == "-x265-params").count(), 1);
+        assert_eq!(
+            params[1],
+            &format!("crf=20:pass={}:stats={}", pass, passlog.replace(':', "\\:"))
+        );
+    }
+}